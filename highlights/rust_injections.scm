@@ -0,0 +1,36 @@
+;; Copyright (c) 2017 Maxim Sokolov
+;; Source: https://github.com/tree-sitter/tree-sitter-rust/blob/master/queries/injections.scm (modified, SQL injections added)
+;; License: MIT — full text included in licenses/tree-sitter-rust.MIT.txt
+;; This file is distributed under the MIT License.
+
+;; Unchanged upstream patterns
+((macro_invocation
+  (token_tree) @injection.content)
+ (#set! injection.language "rust")
+ (#set! injection.include-children))
+
+((macro_rule
+  (token_tree) @injection.content)
+ (#set! injection.language "rust")
+ (#set! injection.include-children))
+
+;; Added: SQL in string arguments of query functions and methods, e.g. `conn.execute("...", [])`
+((call_expression
+  function: [
+    (identifier) @_name
+    (scoped_identifier name: (identifier) @_name)
+    (field_expression field: (field_identifier) @_name)
+  ]
+  arguments: (arguments (string_literal (string_content) @injection.content)))
+ (#any-of? @_name "query" "query_as" "query_scalar" "execute" "execute_batch" "prepare" "prepare_cached" "query_row")
+ (#set! injection.language "sql"))
+
+((call_expression
+  function: [
+    (identifier) @_name
+    (scoped_identifier name: (identifier) @_name)
+    (field_expression field: (field_identifier) @_name)
+  ]
+  arguments: (arguments (raw_string_literal (string_content) @injection.content)))
+ (#any-of? @_name "query" "query_as" "query_scalar" "execute" "execute_batch" "prepare" "prepare_cached" "query_row")
+ (#set! injection.language "sql"))
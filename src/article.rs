@@ -1,66 +1,280 @@
+use std::sync::Arc;
 use crate::trim_offset::TrimOffsets as _;
+use crate::front_matter::{ self, Format };
+use crate::slug::slugify;
+use crate::markdown::Extension;
+use minijinja::value::{ Object, ObjectRepr };
+use minijinja::{ Error, ErrorKind, State, Value };
+use serde::ser::SerializeStruct as _;
 
 #[derive(Debug, PartialEq)]
 pub struct Article {
-    pub ts: Datetime,
-    name:   std::ops::Range<usize>,
-    body:   Option<std::ops::Range<usize>>,
-    data:   String,
+    pub ts:          Datetime,
+    pub updated:     Option<Datetime>,
+    pub description: Option<String>,
+    pub tags:        Vec<String>,
+    pub categories:  Vec<String>,
+    pub author:      Option<String>,
+    pub slug:        Option<String>,
+    pub draft:       bool,
+    pub toc:         bool,
+    /// Markdown extensions switched on or off for this article
+    pub markdown:    Vec<(Extension, bool)>,
+    /// Output directory of co-located files for a page bundle, e.g. "2024/my-post/"
+    pub assets:      Option<String>,
+    name:            String,
+    body:            Option<std::ops::Range<usize>>,
+    data:            String,
 }
 
 impl Article {
-    pub fn new(s: String) -> Result<Self, &'static str> {
+    pub fn new(s: String) -> Result<Self, String> {
+        match s.lines().next().and_then(Format::from_delimiter) {
+            Some(format) => Self::with_front_matter(s, format),
+            None => Self::with_header_line(s),
+        }
+    }
+
+    fn with_header_line(s: String) -> Result<Self, String> {
         if 
             let Some(delimiter) = s.find(' ') &&
             let Some(newline) = s.find('\n') &&
             let Some(ts) = s.get(..delimiter) &&
             let Some(name) = s.get(delimiter + 1..newline) &&
-            let name_trim = name.trim_offsets() &&
-            name_trim.length > 0 &&
+            let name = name.trim() &&
+            !name.is_empty() &&
             let Ok(ts) = ts.parse::<u64>()
         {
-            let name_start = delimiter + 1 + name_trim.left;
-            let name_range = name_start..name_start + name_trim.length;
             Ok(
                 Self {
-                    ts: Datetime(ts),
-                    name: name_range.clone(),
-                    body: s.get(newline + 1..).and_then(|s| {
-                        let body_trim = s.trim_offsets();
-                        match body_trim.length {
-                            0 => None,
-                            n => {
-                                let body_start = newline + 1 + body_trim.left;
-                                Some(body_start..body_start + n)
-                            },
-                        }
-                    }),
-                    data: s,
+                    ts:          Datetime(ts),
+                    updated:     None,
+                    description: None,
+                    tags:        Vec::new(),
+                    categories:  Vec::new(),
+                    author:      None,
+                    slug:        None,
+                    draft:       false,
+                    toc:         true,
+                    markdown:    Vec::new(),
+                    assets:      None,
+                    name:        name.to_string(),
+                    body:        body_range(&s, newline + 1),
+                    data:        s,
                 }
             )
         } else {
-            Err("can't parse article, expected '123456789 Article name\\nArticle Body'")
+            Err("can't parse article, expected '123456789 Article name\\nArticle Body' or front matter".to_string())
         }       
     }
 
+    fn with_front_matter(s: String, format: Format) -> Result<Self, String> {
+        let delimiter = format.delimiter();
+        let start = s.find('\n').map_or(s.len(), |i| i + 1);
+        let (end, body_start) = s[start..]
+            .split_inclusive('\n')
+            .scan(start, |offset, line| {
+                let line_start = *offset;
+                *offset += line.len();
+                Some((line_start, *offset, line))
+            })
+            .find_map(|(line_start, line_end, line)| (line.trim_end() == delimiter).then_some((line_start, line_end)))
+            .ok_or_else(|| format!("front matter isn't closed with '{delimiter}'"))?;
+
+        let v = front_matter::parse(&s[start..end], format).map_err(|e| format!("front matter {e}"))?;
+        let date = |v: front_matter::Scalar| Datetime::parse(&v.to_string()).ok_or_else(|| format!("invalid date \"{v}\""));
+
+        Ok(
+            Self {
+                ts:          date(v.date.ok_or("front matter has no 'date'")?)?,
+                updated:     v.updated.map(date).transpose()?,
+                description: v.description,
+                tags:        terms(v.tags.unwrap_or_default())?,
+                categories:  terms(v.categories.unwrap_or_default())?,
+                author:      v.author,
+                slug:        v.slug.map(url_slug).transpose()?,
+                draft:       v.draft,
+                toc:         v.toc.unwrap_or(true),
+                markdown:    v.markdown.unwrap_or_default().iter().map(|v| extension(v)).collect::<Result<_, _>>()?,
+                assets:      None,
+                name: v.title.filter(|v| !v.trim().is_empty()).ok_or("front matter has no 'title'")?.trim().to_string(),
+                body: body_range(&s, body_start),
+                data: s,
+            }
+        )
+    }
+
     pub fn name(&self) -> &str {
-        self.data.get(self.name.clone()).unwrap()
+        &self.name
     }
 
     pub fn body(&self) -> Option<&str> {
         self.body.as_ref().and_then(|r| self.data.get(r.clone()))
     }
+
+    /// 1-based line of the file where the body starts
+    pub fn body_line(&self) -> usize {
+        self.body.as_ref().map_or(1, |r| self.data[..r.start].matches('\n').count() + 1)
+    }
+
+    /// 1-based line of the file of an offset in the body
+    pub fn line(&self, offset: usize) -> usize {
+        self.body_line() + self.body().and_then(|v| v.get(..offset)).map_or(0, |v| v.matches('\n').count())
+    }
+
+    pub fn modified(&self) -> Datetime {
+        self.updated.unwrap_or(self.ts)
+    }
+
+    /// Absolute prefix of relative image links for feeds, empty unless it's a page bundle
+    pub fn assets_url(&self, base_url: &str) -> String {
+        self.assets.as_deref().map(|v| format!("{base_url}/{v}")).unwrap_or_default()
+    }
+}
+
+impl serde::Serialize for Article {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut v = s.serialize_struct("Article", 14)?;
+        v.serialize_field("name", self.name())?;
+        v.serialize_field("ts", &self.ts)?;
+        v.serialize_field("updated", &self.updated)?;
+        v.serialize_field("modified", &self.modified())?;
+        v.serialize_field("description", &self.description)?;
+        v.serialize_field("tags", &self.tags)?;
+        v.serialize_field("categories", &self.categories)?;
+        v.serialize_field("author", &self.author)?;
+        v.serialize_field("slug", &self.slug)?;
+        v.serialize_field("draft", &self.draft)?;
+        v.serialize_field("toc", &self.toc)?;
+        v.serialize_field("markdown", &self.markdown)?;
+        v.serialize_field("assets", &self.assets)?;
+        v.serialize_field("body", &self.body())?;
+        v.end()
+    }
+}
+
+/// Slugs replace the file name in the output path, so they must be already slugified
+fn url_slug(v: String) -> Result<String, String> {
+    match slugify(&v) {
+        s if s == v => Ok(v),
+        s if s.is_empty() => Err(format!("slug {v:?} has no letters or digits")),
+        s => Err(format!("slug {v:?} isn't URL-safe, use {s:?}")),
+    }
+}
+
+/// "footnotes" enables an extension, "no-footnotes" disables it
+fn extension(v: &str) -> Result<(Extension, bool), String> {
+    let (name, enabled) = v.strip_prefix("no-").map_or((v, true), |v| (v, false));
+    <Extension as clap::ValueEnum>::from_str(name, false)
+        .map(|v| (v, enabled))
+        .map_err(|_| format!("unknown markdown extension {v:?}"))
+}
+
+fn terms(v: Vec<String>) -> Result<Vec<String>, String> {
+    match v.iter().find(|v| slugify(v).is_empty()) {
+        Some(v) => Err(format!("term {v:?} has no letters or digits")),
+        None => Ok(v),
+    }
+}
+
+fn body_range(s: &str, start: usize) -> Option<std::ops::Range<usize>> {
+    s.get(start..).and_then(|s| {
+        let body_trim = s.trim_offsets();
+        match body_trim.length {
+            0 => None,
+            n => {
+                let body_start = start + body_trim.left;
+                Some(body_start..body_start + n)
+            },
+        }
+    })
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub struct Datetime(pub u64);
 
-impl std::fmt::Display for Datetime {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Datetime {
+    /// Accepts unix seconds, "2024-01-05", "2024-01-05 10:00" and RFC 3339 "2024-01-05T10:00:00+02:00"
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+
+        if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+            return s.parse().ok().map(Datetime);
+        }
+
+        let (date, time) = match s.find(['T', 't', ' ']) {
+            Some(i) => (&s[..i], Some(&s[i + 1..])),
+            None => (s, None),
+        };
+
+        let mut date = date.splitn(3, '-').map(|v| v.parse::<u32>().ok());
+        let (Some(Some(y)), Some(Some(m)), Some(Some(d))) = (date.next(), date.next(), date.next()) else {
+            return None;
+        };
+        if !(1970..=9999).contains(&y) || !(1..=12).contains(&m) || d == 0 || d > days_in_month(y, m) {
+            return None;
+        }
+
+        let (seconds, offset) = match time {
+            None => (0, 0),
+            Some(time) => {
+                let (time, offset) = match time.find(['Z', 'z', '+', '-']) {
+                    Some(i) => (&time[..i], parse_offset(&time[i..])?),
+                    None => (time, 0),
+                };
+                let time = time.split('.').next()?;
+                let mut time = time.split(':').map(|v| (v.len() == 2).then(|| v.parse::<u64>().ok()).flatten());
+                let h = time.next()??;
+                let m = time.next()??;
+                let s = time.next().unwrap_or(Some(0))?;
+                if time.next().is_some() || h > 23 || m > 59 || s > 60 {
+                    return None;
+                }
+                (h * 3600 + m * 60 + s, offset)
+            },
+        };
+
+        const DAYS_FROM_0000_03_01_TO_1970_01_01: i64 = 719468;
+        let y = if m <= 2 { y - 1 } else { y } as i64;
+        let era = y / 400;
+        let year_of_era = y - era * 400;
+        let month_prime = if m > 2 { m - 3 } else { m + 9 } as i64;
+        let day_of_year = (153 * month_prime + 2) / 5 + d as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146097 + day_of_era - DAYS_FROM_0000_03_01_TO_1970_01_01;
+
+        u64::try_from(days * 86400 + seconds as i64 - offset).ok().map(Datetime)
+    }
+}
+
+fn days_in_month(y: u32, m: u32) -> u32 {
+    match m {
+        2 if y.is_multiple_of(4) && (!y.is_multiple_of(100) || y.is_multiple_of(400)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn parse_offset(s: &str) -> Option<i64> {
+    let (sign, offset) = match s.as_bytes().first()? {
+        b'Z' | b'z' if s.len() == 1 => return Some(0),
+        b'+' => (1, &s[1..]),
+        b'-' => (-1, &s[1..]),
+        _ => return None,
+    };
+    let (h, m) = offset.split_once(':').unwrap_or((offset.get(..2)?, offset.get(2..)?));
+    if h.len() != 2 || m.len() != 2 {
+        return None;
+    }
+    Some(sign * (h.parse::<i64>().ok()? * 3600 + m.parse::<i64>().ok()? * 60))
+}
+
+impl Datetime {
+    fn civil(self) -> (i32, u32, u32) {
         const SECONDS_PER_DAY: u64 = 86400;
         const DAYS_TO_0000_03_01: i64 = 719468;
         const DAYS_PER_ERA: i64 = 146097;
-        const MONTHS: [&'static str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
 
         let total_days = (self.0 / SECONDS_PER_DAY) as i64 + DAYS_TO_0000_03_01;
         let era = total_days / DAYS_PER_ERA;
@@ -75,10 +289,86 @@ impl std::fmt::Display for Datetime {
             y += 1;
         }
 
+        (y, m, d)
+    }
+
+    fn time(self) -> (u64, u64, u64) {
+        let s = self.0 % 86400;
+        (s / 3600, s / 60 % 60, s % 60)
+    }
+
+    /// "2026-02-04T11:05:00Z", used by Atom feeds and sitemaps
+    pub fn rfc3339(self) -> String {
+        let (y, m, d) = self.civil();
+        let (hh, mm, ss) = self.time();
+        format!("{y:04}-{m:02}-{d:02}T{hh:02}:{mm:02}:{ss:02}Z")
+    }
+
+    /// "Wed, 04 Feb 2026 11:05:00 GMT", used by RSS feeds
+    pub fn rfc822(self) -> String {
+        const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+        const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+        let (y, m, d) = self.civil();
+        let (hh, mm, ss) = self.time();
+        let weekday = WEEKDAYS[(self.0 / 86400 % 7) as usize];
+        format!("{weekday}, {d:02} {} {y} {hh:02}:{mm:02}:{ss:02} GMT", MONTHS[(m - 1) as usize])
+    }
+}
+
+impl std::fmt::Display for Datetime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+
+        let (y, m, d) = self.civil();
         write!(f, "{d:02} {} {y}", MONTHS[(m - 1) as usize])
     }
 }
 
+/// Unix seconds, in templates an object displayed like `Datetime`
+/// with "unix" attribute and "rfc3339()", "rfc822()" methods
+impl serde::Serialize for Datetime {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match minijinja::value::serializing_for_value() {
+            true => Value::from_object(*self).serialize(s),
+            false => s.serialize_u64(self.0),
+        }
+    }
+}
+
+impl Object for Datetime {
+    fn repr(self: &Arc<Self>) -> ObjectRepr {
+        ObjectRepr::Plain
+    }
+
+    fn get_value(self: &Arc<Self>, key: &Value) -> Option<Value> {
+        match key.as_str()? {
+            "unix" => Some(Value::from(self.0)),
+            _ => None,
+        }
+    }
+
+    fn call_method(self: &Arc<Self>, _: &State<'_, '_>, method: &str, args: &[Value]) -> Result<Value, Error> {
+        let value = match method {
+            "rfc3339" => self.rfc3339(),
+            "rfc822" => self.rfc822(),
+            _ => return Err(Error::from(ErrorKind::UnknownMethod)),
+        };
+        match args.is_empty() {
+            true => Ok(Value::from(value)),
+            false => Err(Error::from(ErrorKind::TooManyArguments)),
+        }
+    }
+
+    fn custom_cmp(self: &Arc<Self>, other: &minijinja::value::DynObject) -> Option<std::cmp::Ordering> {
+        Some(self.0.cmp(&other.downcast_ref::<Self>()?.0))
+    }
+
+    fn render(self: &Arc<Self>, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&**self, f)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -107,8 +397,84 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_front_matter_article_parsing() {
+        [
+            ("---\ntitle: My Post\ndate: 2026-02-04\n---\nContent here",                 Some((1770163200, "My Post",    Some("Content here")))),
+            ("+++\ntitle = \"My Post\"\ndate = 1674547200\n+++\n\n  Text  \n",          Some((1674547200, "My Post",    Some("Text")))),
+            ("---\ntitle: Non ascїї\ndate: 2026-02-04T11:05:00Z\n---\n",                  Some((1770203100, "Non ascїї",  None))),
+            ("---\ntitle: Tags\ndate: 1\ntags: [rust, perf]\ndraft: true\n---\nBody",    Some((1,          "Tags",       Some("Body")))),
+            ("---\ntitle: No date\n---\nBody",                                            None),
+            ("---\ndate: 1\n---\nBody",                                                   None),
+            ("---\ntitle: Unclosed\ndate: 1\nBody",                                       None),
+            ("---\ntitle: Unknown\ndate: 1\ncolor: red\n---\nBody",                      Some((1,          "Unknown",    Some("Body")))),
+            ("---\ntitle: Bad date\ndate: 2024-13-01\n---\nBody",                          None),
+            ("---\ntitle: Bad tag\ndate: 1\ntags: [rust, \"++\"]\n---\nBody",                None),
+            ("---\ntitle: Bad draft\ndate: 1\ndraft: maybe\n---\nBody",                  None),
+            ("+++\ntitle: Wrong separator\ndate: 1\n+++\nBody",                           None),
+        ].into_iter().enumerate().for_each(|(i, (input, expected))| {
+            let result = Article::new(input.to_string());
+
+            match (&result, expected) {
+                (Err(_), None) => (),
+                (Ok(a), Some((ts, name, body))) if a.ts == Datetime(ts) && a.name() == name && a.body() == body => (),
+                _ => panic!("\n[Test {i} failed]\nInput: {input:?}\nResult: {result:?}\nExpected matches: {expected:?}\n"),
+            }
+        });
+
+        let article = Article::new("---\ntitle: T\ndate: 1\nupdated: 2\ndescription: D\ntags:\n  - a\n  - b\ncategories: [c]\nauthor: A\nslug: s\ndraft: true\ntoc: false\n---\n".to_string()).unwrap();
+        assert_eq!(article.updated, Some(Datetime(2)));
+        assert_eq!(article.description.as_deref(), Some("D"));
+        assert_eq!(article.tags, ["a", "b"]);
+        assert_eq!(article.categories, ["c"]);
+        assert_eq!(article.author.as_deref(), Some("A"));
+        assert_eq!(article.slug.as_deref(), Some("s"));
+        assert!(article.draft);
+        assert!(!article.toc);
+
+        let article = Article::new("---\ntitle: T\ndate: 1\ntags:\ndescription: |\n  Line\n  break\nextra:\n  key: v\n---\n".to_string()).unwrap();
+        assert_eq!((article.tags.len(), article.description.as_deref()), (0, Some("Line\nbreak\n")));
+        let article = Article::new("+++\ntitle = \"T\"\ndate = 2026-02-04T11:05:00Z\ntags = [\n  \"a\",\n]\n+++\n".to_string()).unwrap();
+        assert_eq!((article.ts, article.tags.as_slice()), (Datetime(1770203100), ["a".to_string()].as_slice()));
+
+        let article = Article::new("---\ntitle: T\ndate: 1\nmarkdown: [footnotes, no-math]\n---\n".to_string()).unwrap();
+        assert_eq!(article.markdown, [(Extension::Footnotes, true), (Extension::Math, false)]);
+        assert!(Article::new("---\ntitle: T\ndate: 1\nmarkdown: [unknown]\n---\n".to_string()).is_err());
+
+        for slug in ["my-post", "пост_2", "\"x\""] {
+            assert!(Article::new(format!("---\ntitle: T\ndate: 1\nslug: {slug}\n---\n")).is_ok(), "{slug}");
+        }
+        for slug in ["My Post", "a/b", "../x", "-a", "!!"] {
+            assert!(Article::new(format!("---\ntitle: T\ndate: 1\nslug: {slug}\n---\n")).is_err(), "{slug}");
+        }
+    }
+
+    #[test]
+    fn test_datetime_parsing() {
+        [
+            ("1770203100",                Some(1770203100)),
+            ("1970-01-01",                Some(0)),
+            ("2026-02-04",                Some(1770163200)),
+            ("2026-02-04 11:05",          Some(1770203100)),
+            ("2026-02-04T11:05:00Z",      Some(1770203100)),
+            ("2026-02-04T13:05:00+02:00", Some(1770203100)),
+            ("2026-02-04T10:05:00.5-01:00", Some(1770203100)),
+            ("2024-02-29",                Some(1709164800)),
+            ("2023-02-29",                None),
+            ("2026-13-01",                None),
+            ("2026-02-04T25:00",          None),
+            ("04 feb 2026",               None),
+            ("",                          None),
+        ].into_iter().for_each(|(input, expected)| {
+            assert_eq!(Datetime::parse(input), expected.map(Datetime), "Input: {input:?}");
+        });
+    }
+
     #[test]
     fn test_datetime_formatting() {
-        assert_eq!(&format!("{}", Datetime(1770203100)), "04 feb 2026")
+        assert_eq!(&format!("{}", Datetime(1770203100)), "04 feb 2026");
+        assert_eq!(Datetime(1770203100).rfc3339(), "2026-02-04T11:05:00Z");
+        assert_eq!(Datetime(1770203100).rfc822(), "Wed, 04 Feb 2026 11:05:00 GMT");
+        assert_eq!(Datetime(0).rfc822(), "Thu, 01 Jan 1970 00:00:00 GMT");
     }
 }
\ No newline at end of file
@@ -0,0 +1,105 @@
+// INFO Article headers, YAML between "---" lines or TOML between "+++" lines,
+// unknown keys are ignored, so articles can carry data of other tools
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    pub fn from_delimiter(line: &str) -> Option<Self> {
+        match line.trim_end() {
+            "---" => Some(Format::Yaml),
+            "+++" => Some(Format::Toml),
+            _     => None,
+        }
+    }
+
+    pub fn delimiter(self) -> &'static str {
+        match self {
+            Format::Yaml => "---",
+            Format::Toml => "+++",
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct FrontMatter {
+    pub title:       Option<String>,
+    pub date:        Option<Scalar>,
+    pub updated:     Option<Scalar>,
+    pub description: Option<String>,
+    pub tags:        Option<Vec<String>>,
+    pub categories:  Option<Vec<String>>,
+    pub author:      Option<String>,
+    pub slug:        Option<String>,
+    pub draft:       bool,
+    pub toc:         Option<bool>,
+    pub markdown:    Option<Vec<String>>,
+}
+
+/// Dates are strings or numbers in YAML and also datetimes in TOML, e.g. "date = 2024-01-05"
+#[derive(Debug, PartialEq, serde::Deserialize)]
+#[serde(untagged)]
+pub enum Scalar {
+    Number(u64),
+    String(String),
+    Datetime(toml::value::Datetime),
+}
+
+impl std::fmt::Display for Scalar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scalar::Number(v) => write!(f, "{v}"),
+            Scalar::String(v) => write!(f, "{v}"),
+            Scalar::Datetime(v) => write!(f, "{v}"),
+        }
+    }
+}
+
+pub fn parse(s: &str, format: Format) -> Result<FrontMatter, String> {
+    match format {
+        _ if s.trim().is_empty() => Ok(FrontMatter::default()),
+        Format::Yaml => serde_yaml::from_str(s).map_err(|e| e.to_string()),
+        Format::Toml => toml::from_str(s).map_err(|e| e.message().to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_front_matter_parsing() {
+        let string = |v: &str| Some(v.to_string());
+        let list = |v: &[&str]| Some(v.iter().map(|v| v.to_string()).collect::<Vec<_>>());
+
+        let v = parse("title: \"Quoted: \\\"yes\\\"\"\ndraft: true # comment\n\n# comment\ntags: [rust, \"a, b\", 'c']", Format::Yaml).unwrap();
+        assert_eq!((v.title, v.draft, v.tags), (string("Quoted: \"yes\""), true, list(&["rust", "a, b", "c"])));
+
+        let v = parse("tags:\n  - rust\n  - perf\ndescription: >\n  Folded\n  text\nauthor:\ndraft: false", Format::Yaml).unwrap();
+        assert_eq!((v.tags, v.description, v.author, v.draft), (list(&["rust", "perf"]), string("Folded text\n"), None, false));
+
+        let v = parse("title: T\ndate: 2024-01-05\nextra:\n  nested: [1, 2]\ncolor: red", Format::Yaml).unwrap();
+        assert_eq!((v.title, v.date), (string("T"), Some(Scalar::String("2024-01-05".into()))));
+        assert_eq!(parse("date: 1", Format::Yaml).unwrap().date, Some(Scalar::Number(1)));
+
+        let v = parse("title = \"Hello = World\"\ndate = 2024-01-05\ntags = [\n  \"a\",\n  \"b\",\n]\n[extra]\nkey = 1", Format::Toml).unwrap();
+        assert_eq!((v.title, v.date.map(|v| v.to_string()), v.tags), (string("Hello = World"), string("2024-01-05"), list(&["a", "b"])));
+        assert_eq!(parse("", Format::Toml).unwrap(), FrontMatter::default());
+
+        for (input, format) in [
+            ("title Hello: [",           Format::Yaml),
+            ("title: a\ntitle: b",       Format::Yaml),
+            ("tags: [a, b",              Format::Yaml),
+            ("draft: maybe",             Format::Yaml),
+            ("tags: {a: 1}",             Format::Yaml),
+            ("title: Hello",             Format::Toml),
+            ("my key = 1",               Format::Toml),
+        ] {
+            assert!(parse(input, format).is_err(), "{input:?}");
+        }
+    }
+}
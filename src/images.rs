@@ -0,0 +1,182 @@
+// INFO Dimensions of local images for the width and height attributes, read from the copied files,
+// with the "images" feature JPEG and PNG files also get resized variants for srcset, PNG ones WebP versions
+
+use std::path::Path;
+
+/// Widths of resized variants, only the ones smaller than the original are generated
+pub const WIDTHS: &[u32] = &[480, 960, 1440];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Jpeg,
+    Png,
+    WebP,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width:  u32,
+    pub height: u32,
+    /// Generated variants with their widths, the original included, empty without variants
+    pub srcset: Vec<(String, u32)>,
+    pub webp:   Vec<(String, u32)>,
+}
+
+/// Local images of a page by their links
+#[derive(Debug, Default)]
+pub struct Images(std::collections::BTreeMap<String, Image>);
+
+impl Images {
+    /// `page` is the output path relative to `output`, e.g. "2024/my-post.html", links are the rendered ones,
+    /// `exists` is called with paths of images and their variants
+    pub fn new(output: &Path, page: &str, links: impl IntoIterator<Item = String>, exists: impl Fn(&Path) -> bool) -> Self {
+        let mut result = std::collections::BTreeMap::new();
+        for link in links {
+            if result.contains_key(&link) || link.contains(['?', '#']) {
+                continue;
+            }
+            let Some((Some(path), _)) = crate::links::resolve(page, &link) else {
+                continue;
+            };
+            let path = output.join(path);
+            if !exists(&path) {
+                continue;
+            }
+            let Ok(size) = imagesize::size(&path) else {
+                continue;
+            };
+
+            let (width, height) = (size.width as u32, size.height as u32);
+            let mut image = Image { width, height, srcset: Vec::new(), webp: Vec::new() };
+            let name = path.file_name().map(|v| v.to_string_lossy().into_owned()).unwrap_or_default();
+            for ((url, w, format), (file, _, _)) in variants(&link, width).into_iter().zip(variants(&name, width)) {
+                if exists(&path.with_file_name(file)) {
+                    match format {
+                        Format::WebP => image.webp.push((url, w)),
+                        _ => image.srcset.push((url, w)),
+                    }
+                }
+            }
+            if !image.srcset.is_empty() {
+                image.srcset.push((link.clone(), width));
+            }
+            result.insert(link, image);
+        }
+        Self(result)
+    }
+
+    pub fn get(&self, link: &str) -> Option<&Image> {
+        self.0.get(link)
+    }
+}
+
+impl FromIterator<(String, Image)> for Images {
+    fn from_iter<T: IntoIterator<Item = (String, Image)>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+/// Variants of an image `width` pixels wide with their widths and formats,
+/// e.g. "a-480w.png", "a-480w.webp" and "a.webp" for "a.png", empty unless it's JPEG or PNG
+pub fn variants(name: &str, width: u32) -> Vec<(String, u32, Format)> {
+    let Some((stem, ext)) = name.rsplit_once('.') else {
+        return Vec::new();
+    };
+    let format = match ext.to_ascii_lowercase().as_str() {
+        "jpg" | "jpeg" => Format::Jpeg,
+        "png" => Format::Png,
+        _ => return Vec::new(),
+    };
+
+    let widths = WIDTHS.iter().copied().filter(|&w| w < width);
+    let mut result = widths.clone().map(|w| (format!("{stem}-{w}w.{ext}"), w, format)).collect::<Vec<_>>();
+    if format == Format::Png {
+        result.extend(widths.map(|w| (format!("{stem}-{w}w.webp"), w, Format::WebP)));
+        result.push((format!("{stem}.webp"), width, Format::WebP));
+    }
+    result
+}
+
+/// Writes resized and converted versions of an encoded image, WebP ones are lossless
+#[cfg(feature = "images")]
+pub fn generate(data: &[u8], variants: &[(std::path::PathBuf, u32, Format)]) -> Result<(), String> {
+    let original = image::load_from_memory(data).map_err(|e| e.to_string())?;
+    for (path, width, format) in variants {
+        let resized = match *width < original.width() {
+            true => original.resize(*width, u32::MAX, image::imageops::FilterType::Lanczos3),
+            false => original.clone(),
+        };
+        let (resized, format) = match format {
+            Format::Jpeg => (image::DynamicImage::ImageRgb8(resized.to_rgb8()), image::ImageFormat::Jpeg),
+            Format::Png => (resized, image::ImageFormat::Png),
+            Format::WebP => (image::DynamicImage::ImageRgba8(resized.to_rgba8()), image::ImageFormat::WebP),
+        };
+        resized.save_with_format(path, format).map_err(|e| format!("{path:?} - {e}"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variants() {
+        let names = |name, width| variants(name, width).into_iter().map(|(v, w, _)| format!("{v} {w}")).collect::<Vec<_>>();
+        assert_eq!(names("a.jpg", 1000), ["a-480w.jpg 480", "a-960w.jpg 960"]);
+        assert_eq!(names("p/a.b.PNG", 600), ["p/a.b-480w.PNG 480", "p/a.b-480w.webp 480", "p/a.b.webp 600"]);
+        assert_eq!(names("a.png", 480), ["a.webp 480"]);
+        assert_eq!(names("a.gif", 2000), Vec::<String>::new());
+        assert_eq!(names("noext", 2000), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_images() {
+        let output = crate::test_utils::TempDir::new("images");
+        std::fs::create_dir_all(output.join("2024/post")).unwrap();
+        let gif = |width: u16, height: u16| [b"GIF89a".as_slice(), &width.to_le_bytes(), &height.to_le_bytes(), &[0; 16]].concat();
+        std::fs::write(output.join("2024/post/a.gif"), gif(640, 480)).unwrap();
+        std::fs::write(output.join("logo.gif"), gif(32, 16)).unwrap();
+        std::fs::write(output.join("2024/post/broken.gif"), b"not an image").unwrap();
+
+        let links = ["post/a.gif", "/logo.gif", "post/broken.gif", "missing.gif", "https://x.org/a.gif"].map(String::from);
+        let images = Images::new(&output, "2024/post.html", links, |v| v.is_file());
+        assert_eq!(images.get("post/a.gif"), Some(&Image { width: 640, height: 480, srcset: Vec::new(), webp: Vec::new() }));
+        assert_eq!(images.get("/logo.gif").map(|v| (v.width, v.height)), Some((32, 16)));
+        assert_eq!(images.get("post/broken.gif"), None);
+        assert_eq!(images.get("missing.gif"), None);
+        assert_eq!(images.get("https://x.org/a.gif"), None);
+
+        // Only variants that exist are used, a PNG header is enough for its dimensions
+        let png = [
+            b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".as_slice(), &1000u32.to_be_bytes(), &500u32.to_be_bytes(), &[8, 6, 0, 0, 0, 0, 0, 0, 0],
+        ].concat();
+        std::fs::write(output.join("2024/post/b%20c.png"), png).unwrap();
+        let generated = ["2024/post/b%20c.png", "2024/post/b%20c-480w.png", "2024/post/b%20c.webp"].map(|v| output.join(v));
+        let images = Images::new(&output, "2024/post.html", ["post/b%2520c.png".to_string()], |v| generated.iter().any(|g| g == v));
+        assert_eq!(images.get("post/b%2520c.png"), Some(&Image {
+            width:  1000,
+            height: 500,
+            srcset: vec![("post/b%2520c-480w.png".to_string(), 480), ("post/b%2520c.png".to_string(), 1000)],
+            webp:   vec![("post/b%2520c.webp".to_string(), 1000)],
+        }));
+    }
+
+    #[test]
+    #[cfg(feature = "images")]
+    fn test_generate() {
+        let output = crate::test_utils::TempDir::new("generate");
+        let mut data = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::new(1000, 500).write_to(&mut data, image::ImageFormat::Png).unwrap();
+
+        let variants = [("a-480w.png", 480, Format::Png), ("a-480w.jpg", 480, Format::Jpeg), ("a.webp", 1000, Format::WebP)]
+            .map(|(v, width, format)| (output.join(v), width, format));
+        generate(data.get_ref(), &variants).unwrap();
+        for (path, expected) in [("a-480w.png", (480, 240, image::ImageFormat::Png)), ("a-480w.jpg", (480, 240, image::ImageFormat::Jpeg)), ("a.webp", (1000, 500, image::ImageFormat::WebP))] {
+            let data = std::fs::read(output.join(path)).unwrap();
+            let size = imagesize::blob_size(&data).unwrap();
+            assert_eq!((size.width as u32, size.height as u32, image::guess_format(&data).unwrap()), expected, "{path:?}");
+        }
+        assert!(generate(b"not an image", &variants).is_err());
+    }
+}
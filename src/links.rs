@@ -0,0 +1,155 @@
+// INFO Checks relative links of rendered articles after the build, against the generated files
+// and heading ids of the target article, external and scheme links like "mailto:" are skipped
+
+use std::path::PathBuf;
+use crate::markdown::Document;
+
+/// Links of a rendered article with their lines in the source file
+pub struct Page {
+    pub src:   PathBuf,
+    /// Output path relative to the output directory, e.g. "2024/my-post.html"
+    pub name:  String,
+    pub links: Vec<(String, usize)>,
+    pub ids:   Vec<String>,
+}
+
+impl Page {
+    pub fn new(src: PathBuf, name: String, article: &crate::article::Article, document: &Document) -> Self {
+        let (links, ids) = document.links();
+        let links = links.into_iter()
+            .map(|(dest, offset)| (dest, article.line(offset)))
+            .collect();
+        Self { src, name, links, ids }
+    }
+}
+
+/// "content/a.md:12 - broken link \"./old.html\"" for every link without a target,
+/// `exists` is called with paths relative to the output directory
+pub fn check(pages: &[Page], exists: impl Fn(&str) -> bool) -> Vec<String> {
+    let ids = pages.iter().map(|v| (v.name.as_str(), &v.ids)).collect::<std::collections::HashMap<_, _>>();
+    let mut result = Vec::new();
+
+    for page in pages {
+        for (dest, line) in &page.links {
+            let Some((path, fragment)) = resolve(&page.name, dest) else {
+                continue;
+            };
+
+            let found = match &path {
+                Some(path) if path.is_empty() || path.ends_with('/') => exists(&format!("{path}index.html")),
+                Some(path) => exists(path) || exists(&format!("{path}/index.html")),
+                None => false,
+            };
+            let found = found && match (fragment.as_str(), ids.get(path.as_deref().unwrap_or_default())) {
+                ("", _) => true,
+                (fragment, Some(ids)) => ids.iter().any(|v| v == fragment),
+                (_, None) => true,
+            };
+
+            if !found {
+                result.push(format!("{}:{line} - broken link {dest:?}", page.src.display()));
+            }
+        }
+    }
+
+    result
+}
+
+/// Output path and fragment a link of `from` points to, `None` for external links,
+/// `Some((None, _))` for paths above the output directory
+pub fn resolve(from: &str, dest: &str) -> Option<(Option<String>, String)> {
+    let scheme = dest.split_once(':').is_some_and(|(v, _)| !v.is_empty() && v.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)));
+    if scheme || dest.starts_with("//") {
+        return None;
+    }
+
+    let (path, fragment) = dest.split_once('#').unwrap_or((dest, ""));
+    let path = path.split_once('?').map_or(path, |v| v.0);
+    let fragment = decode(fragment);
+    if path.is_empty() {
+        return Some((Some(from.to_string()), fragment));
+    }
+
+    let mut parts = match path.starts_with('/') {
+        true => Vec::new(),
+        false => from.split('/').collect::<Vec<_>>().split_last().map(|v| v.1.to_vec()).unwrap_or_default(),
+    };
+    let path = decode(path);
+    for part in path.split('/') {
+        match part {
+            "" | "." => (),
+            ".." => if parts.pop().is_none() {
+                return Some((None, fragment));
+            },
+            part => parts.push(part),
+        }
+    }
+
+    let mut result = parts.join("/");
+    if path.ends_with('/') && !result.is_empty() {
+        result.push('/');
+    }
+    Some((Some(result), fragment))
+}
+
+/// Percent-decoding, invalid sequences are kept as is
+fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], s.get(i + 1..i + 3).and_then(|v| u8::from_str_radix(v, 16).ok())) {
+            (b'%', Some(b)) => { result.push(b); i += 3; },
+            (b, _) => { result.push(b); i += 1; },
+        }
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        [
+            ("2024/a.html", "b.html",              Some((Some("2024/b.html"), ""))),
+            ("2024/a.html", "./b.html#x%20y",      Some((Some("2024/b.html"), "x y"))),
+            ("2024/a.html", "../tags/",            Some((Some("tags/"), ""))),
+            ("2024/a.html", "/css/a%20b.css?v=1",  Some((Some("css/a b.css"), ""))),
+            ("2024/a.html", "#top",                Some((Some("2024/a.html"), "top"))),
+            ("a.html",      "../../x.html",        Some((None, ""))),
+            ("a.html",      "/",                   Some((Some(""), ""))),
+            ("a.html",      "https://x.org/",      None),
+            ("a.html",      "mailto:a@b.c",        None),
+            ("a.html",      "//x.org/a.png",       None),
+        ].into_iter().for_each(|(from, dest, expected)| {
+            let expected = expected.map(|(path, fragment)| (path.map(String::from), fragment.to_string()));
+            assert_eq!(resolve(from, dest), expected, "Link: {dest:?} from {from:?}");
+        });
+    }
+
+    #[test]
+    fn test_check() {
+        let page = |name: &str, links: &[&str], ids: &[&str]| Page {
+            src:   PathBuf::from(format!("content/{name}.md")),
+            name:  format!("{name}.html"),
+            links: links.iter().enumerate().map(|(i, v)| (v.to_string(), i + 1)).collect(),
+            ids:   ids.iter().map(|v| v.to_string()).collect(),
+        };
+        let pages = [
+            page("a", &["b.html#one", "b.html#two", "#self", "#missing", "old.html", "tags/", "img.png", "https://x.org/"], &["self"]),
+            page("b", &["a.html", "../a.html"], &["one"]),
+        ];
+
+        let generated = ["a.html", "b.html", "tags/index.html"];
+        let result = check(&pages, |v| generated.contains(&v));
+        assert_eq!(result, [
+            "content/a.md:2 - broken link \"b.html#two\"",
+            "content/a.md:4 - broken link \"#missing\"",
+            "content/a.md:5 - broken link \"old.html\"",
+            "content/a.md:7 - broken link \"img.png\"",
+            "content/b.md:2 - broken link \"../a.html\"",
+        ]);
+    }
+}
@@ -1,13 +1,8 @@
-use std::path::PathBuf;
+use std::path::{ Path, PathBuf };
 use std::io::BufRead as _;
-use clap::Parser as _;
+use clap::{ CommandFactory as _, FromArgMatches as _ };
 use rayon::prelude::*;
 
-mod trim_offset;
-mod article;
-mod markdown;
-mod tree_sitter_html;
-
 macro_rules! die {
     ($($arg:tt)*) => {{
         eprintln!($($arg)*);
@@ -15,15 +10,41 @@ macro_rules! die {
     }};
 }
 
+mod trim_offset;
+mod article;
+mod front_matter;
+mod markdown;
+mod mathml;
+mod slug;
+mod taxonomy;
+mod serve;
+mod manifest;
+mod links;
+mod images;
+mod search;
+mod site;
+mod template;
+mod theme;
+mod tree_sitter_html;
+#[cfg(test)]
+mod test_utils;
+
 #[derive(clap::Parser, Debug)]
-#[command(author, version, about = "Simple Static Site Generator")]
+#[command(author, version, about = "Simple Static Site Generator", args_override_self = true)]
 struct Config {
+    /// Configuration file with the same options as "key = value" pairs, e.g. "per_page = 10",
+    /// flags given on the command line override it, "site.toml" or "blog.toml" by default
+    #[arg(long)]
+    config: Option<PathBuf>,
+
     /// The name of blog, displayed in page titles and headers
     #[arg(short, long, default_value = "My Blog")]
     sitename: String,
 
-    /// Path to the directory containing source content,
-    /// expected a flat structure with "ascii_alphanumeric_lowercase.md" files
+    /// Path to the directory containing source content, "*.md" files in nested directories
+    /// are mirrored to the output, e.g. "2024/my-post.md" to "2024/my-post.html",
+    /// "2024/my-post/index.md" is a page bundle, its other files are copied to "2024/my-post/".
+    /// Files of "static" next to it are copied to the output unchanged
     #[arg(short, long, default_value = "content")]
     content: PathBuf,
 
@@ -32,44 +53,216 @@ struct Config {
     output: PathBuf,
 
     /// Path to the directory with static assets,
-    /// expected "head.html", "header.html", "footer.html" and intro.md
+    /// expected "head.html", "header.html", "footer.html" and intro.md,
+    /// optional "highlights/<lang>.scm" query overrides and "highlights/<lang>/" with tree-sitter queries and a compiled grammar
     #[arg(short, long, default_value = "assets")]
     assets: PathBuf,
 
+    /// Language of the site, set as the "lang" attribute of pages and in feeds
+    #[arg(long, default_value = "en")]
+    language: String,
+
+    /// Default author of articles without "author" in the front matter
+    #[arg(long)]
+    author: Option<String>,
+
+    /// Absolute URL of the published site, e.g. "https://example.com/blog",
+    /// feeds, "sitemap.xml" and "robots.txt" are generated only when it's set
+    #[arg(short, long)]
+    base_url: Option<String>,
+
+    /// Maximum number of latest articles included in "feed.xml" and "rss.xml"
+    #[arg(long, default_value_t = 20)]
+    feed_limit: usize,
+
+    /// Number of articles per index page, "index.html" is followed by "page/2.html" and so on,
+    /// if not set, all articles are listed on a single page
+    #[arg(long)]
+    per_page: Option<std::num::NonZeroUsize>,
+
     /// Optional list of specific files to process,
     /// if empty, all files in the content directory will be processed
     #[arg(short, long, num_args = 1..)]
     files: Option<Vec<PathBuf>>,
+
+    /// Append a "#" self-link to every heading of articles
+    #[arg(long)]
+    heading_anchors: bool,
+
+    /// Markdown extensions enabled for every article, articles switch them with
+    /// the "markdown" front matter key, e.g. "markdown: [math, no-tables]",
+    /// with "math" text between two "$" signs is TeX, escape them as "\$" in prices
+    #[arg(long, value_delimiter = ',', num_args = 1.., default_values = ["tables", "strikethrough", "metadata-blocks"])]
+    markdown: Vec<markdown::Extension>,
+
+    /// Class names of highlighted code, "short" like "kw" or "full" tree-sitter capture names
+    /// like "keyword.return", "theme" escapes their dots in selectors
+    #[arg(long, value_enum, default_value_t)]
+    highlight_classes: tree_sitter_html::Classes,
+
+    /// Class names of highlighted code in "feed.xml" and "rss.xml",
+    /// feed readers drop stylesheets, so colors are inlined by default
+    #[arg(long, value_enum, default_value_t = tree_sitter_html::Classes::Inline)]
+    feed_highlight_classes: tree_sitter_html::Classes,
+
+    /// Publish drafts and articles scheduled for the future, for local previews
+    #[arg(long)]
+    drafts: bool,
+
+    /// Moment used to decide whether scheduled articles are published,
+    /// unix seconds or "2024-01-05T10:00:00Z", the current time by default
+    #[arg(long, value_parser = parse_datetime)]
+    now: Option<article::Datetime>,
+
+    /// Re-render every article, ignoring hashes stored by the previous build
+    #[arg(long)]
+    force: bool,
+
+    /// Generate "search.html" with a client-side full-text index of published articles
+    #[arg(long)]
+    search: bool,
+
+    /// Exit with an error when relative links of articles point to missing files or headings,
+    /// they are reported as warnings otherwise
+    #[arg(long)]
+    strict: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Build the site, serve the output directory on localhost
+    /// and rebuild with live reload on content, assets or templates changes
+    Serve {
+        /// Local port to listen on
+        #[arg(short, long, default_value_t = 8080)]
+        port: u16,
+    },
+    /// Write "highlight-light.css" and "highlight-dark.css" for the selected "--highlight-classes"
+    Theme {
+        /// Directory where the stylesheets will be saved
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Write the built-in templates in minijinja syntax, to be edited as overrides, existing files are kept
+    Templates {
+        /// Directory where the templates will be saved, "templates" next to the content directory by default
+        dir: Option<PathBuf>,
+    },
 }
 
 #[minificator::template]
-#[derive(askama::Template)]
+#[derive(askama::Template, serde::Serialize)]
 #[template(path = "templates/article.html")]
 pub struct ArticleTemplate<'a> {
-    pub sitename: &'a str,
+    pub site:     &'a site::Site,
     pub title:    &'a str,
+    pub meta:     &'a article::Article,
     pub head:     &'a str,
     pub header:   &'a str,
     pub footer:   &'a str,
+    pub root:     &'a str,
     pub ts:       article::Datetime,
+    pub toc:      Option<markdown::Toc<'a>>,
     pub content:  markdown::Markdown<'a>,
 }
 
 #[minificator::template]
-#[derive(askama::Template)]
+#[derive(askama::Template, serde::Serialize)]
 #[template(path = "templates/index.html")]
 pub struct IndexTemplate<'a> {
-    pub sitename: &'a str,
+    pub site:     &'a site::Site,
     pub head:     &'a str,
     pub header:   &'a str,
     pub footer:   &'a str,
+    pub root:     &'a str,
     pub articles: &'a[&'a (crate::article::Article, String)],
-    pub intro:    markdown::Markdown<'a>,
+    pub intro:    Option<markdown::Markdown<'a>>,
+    pub page:     usize,
+    pub pages:    usize,
+    pub prev:     Option<String>,
+    pub next:     Option<String>,
 }
 
+#[minificator::template]
+#[derive(askama::Template, serde::Serialize)]
+#[template(path = "templates/taxonomy.html")]
+pub struct TaxonomyTemplate<'a> {
+    pub site:     &'a site::Site,
+    pub title:    &'a str,
+    pub head:     &'a str,
+    pub header:   &'a str,
+    pub footer:   &'a str,
+    pub terms:    &'a [taxonomy::Term<'a>],
+}
+
+#[minificator::template]
+#[derive(askama::Template, serde::Serialize)]
+#[template(path = "templates/term.html")]
+pub struct TermTemplate<'a> {
+    pub site:     &'a site::Site,
+    pub title:    &'a str,
+    pub head:     &'a str,
+    pub header:   &'a str,
+    pub footer:   &'a str,
+    pub root:     &'a str,
+    pub term:     &'a taxonomy::Term<'a>,
+}
+
+#[minificator::template]
+#[derive(askama::Template, serde::Serialize)]
+#[template(path = "templates/search.html")]
+pub struct SearchTemplate<'a> {
+    pub site:     &'a site::Site,
+    pub head:     &'a str,
+    pub header:   &'a str,
+    pub footer:   &'a str,
+}
+
+#[minificator::template]
+#[derive(askama::Template, serde::Serialize)]
+#[template(path = "templates/atom.xml")]
+pub struct AtomTemplate<'a> {
+    pub site:     &'a site::Site,
+    pub base_url: &'a str,
+    pub options:  markdown::Options,
+    pub updated:  article::Datetime,
+    pub articles: &'a [&'a (crate::article::Article, String)],
+}
+
+#[minificator::template]
+#[derive(askama::Template, serde::Serialize)]
+#[template(path = "templates/rss.xml")]
+pub struct RssTemplate<'a> {
+    pub site:     &'a site::Site,
+    pub base_url: &'a str,
+    pub options:  markdown::Options,
+    pub updated:  article::Datetime,
+    pub articles: &'a [&'a (crate::article::Article, String)],
+}
+
+#[minificator::template]
+#[derive(askama::Template, serde::Serialize)]
+#[template(path = "templates/sitemap.xml")]
+pub struct SitemapTemplate<'a> {
+    pub base_url: &'a str,
+    pub updated:  article::Datetime,
+    pub articles: &'a [&'a (crate::article::Article, String)],
+}
+
+/// Top-level output directories of generated pages, not allowed in content
+const RESERVED: &[&str] = &["page", "tags", "categories"];
+
+static GENERATED: manifest::Manifest = manifest::Manifest::new();
+
 fn main() {
     let time = std::time::Instant::now();
-    let cfg = Config::parse();
+    let args = std::env::args_os().collect::<Vec<_>>();
+    let command = site::command(Config::command(), &args)
+        .unwrap_or_else(|e| die!("Error: can't load configuration {e}"));
+    let cfg = Config::from_arg_matches(&command.get_matches_from(args)).unwrap_or_else(|e| e.exit());
 
     println!("{} v{}\nUse '--help' for more information.", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
 
@@ -77,63 +270,315 @@ fn main() {
         die!("Error: input dir eq output dir");
     }
 
+    if let Some(Command::Serve { port }) = cfg.command {
+        let watch = [templates_dir(&cfg.content), static_dir(&cfg.content), cfg.content, cfg.assets].into_iter().chain(cfg.config).collect();
+        serve::run(cfg.output, watch, port);
+    }
+
+    if let Some(Command::Theme { dir }) = &cfg.command {
+        std::fs::create_dir_all(dir).unwrap_or_else(|e| die!("Error: can't open directory {dir:?} - {e}"));
+        for (name, dark) in [("highlight-light.css", false), ("highlight-dark.css", true)] {
+            let path = dir.join(name);
+            std::fs::write(&path, theme::css(cfg.highlight_classes, dark)).unwrap_or_else(|e| die!("Error: can't write {path:?} - {e}"));
+            println!("Info: written {path:?}");
+        }
+        return;
+    }
+
+    if let Some(Command::Templates { dir }) = &cfg.command {
+        let dir = dir.clone().unwrap_or_else(|| templates_dir(&cfg.content));
+        std::fs::create_dir_all(&dir).unwrap_or_else(|e| die!("Error: can't open directory {dir:?} - {e}"));
+        for (name, source) in template::DEFAULTS {
+            let path = dir.join(name);
+            if path.exists() {
+                println!("Warning: {path:?} already exists, skipped");
+                continue;
+            }
+            std::fs::write(&path, source).unwrap_or_else(|e| die!("Error: can't write {path:?} - {e}"));
+            println!("Info: written {path:?}");
+        }
+        return;
+    }
+
     let head   = load_asset(&cfg.assets, "head.html", true);
     let header = load_asset(&cfg.assets, "header.html", true);
     let footer = load_asset(&cfg.assets, "footer.html", true);
     let intro  = load_asset(&cfg.assets, "intro.md", false);
+    let highlights = load_highlights(&cfg.assets.join("highlights"));
+    let templates  = load_templates(&templates_dir(&cfg.content));
 
     std::fs::create_dir_all(&cfg.output).unwrap_or_else(|e| die!("Error: can't open output directory {:?} - {e}", cfg.output));
 
-    let articles = load_dir(&cfg.content)
+    let site = site::Site {
+        name:     cfg.sitename.clone(),
+        base_url: cfg.base_url.as_deref().map(|v| v.trim_end_matches('/').to_string()),
+        language: cfg.language.clone(),
+        author:   cfg.author.clone(),
+    };
+    let previous = match cfg.force {
+        true => manifest::Manifest::new(),
+        false => manifest::Manifest::load(&cfg.output),
+    };
+    let options = markdown::Options {
+        anchors:    cfg.heading_anchors,
+        classes:    cfg.highlight_classes,
+        extensions: markdown::Extensions::new(&cfg.markdown),
+    };
+    let inputs = manifest::hash(&[
+        env!("CARGO_PKG_VERSION").as_bytes(),
+        format!("{options:?}").as_bytes(),
+        format!("{site:?}").as_bytes(),
+        head.as_bytes(),
+        header.as_bytes(),
+        footer.as_bytes(),
+        &highlights.to_le_bytes(),
+        &templates.to_le_bytes(),
+    ]);
+    let unchanged = std::sync::atomic::AtomicUsize::new(0);
+    let unpublished = std::sync::atomic::AtomicUsize::new(0);
+    let now = cfg.now.unwrap_or_else(|| article::Datetime(
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |v| v.as_secs())
+    ));
+
+    let static_dir = static_dir(&cfg.content);
+    if static_dir.is_dir() {
+        copy_dir(&static_dir, &cfg.output, &previous, &|_| false);
+    }
+
+    let loaded = load_dir(&cfg.content)
         .unwrap_or_else(|e| die!("Error: can't process content {:?} - {e}", cfg.content))
         .into_par_iter()
-        .map(|(name, src)| {
-            let data = load_article(&src, cfg.files.as_deref()).unwrap_or_else(|e| die!("Error: can't read {src:?} - {e}"));
-            let article = article::Article::new(data).unwrap_or_else(|e| die!("Error: can't parse {src:?} - {e}"));
+        .map(|(dir, stem, src, bundle)| {
+            let partial = cfg.files.as_deref().is_some_and(|v| v.contains(&src));
+            let data = load_article(&src, partial).unwrap_or_else(|e| die!("Error: can't read {src:?} - {e}"));
+            let hash = manifest::hash(&[&inputs.to_le_bytes(), data.as_bytes()]);
+            let mut article = article::Article::new(data).unwrap_or_else(|e| die!("Error: can't parse {src:?} - {e}"));
+            let stem = article.slug.as_deref().unwrap_or(&stem);
+            if bundle && dir.is_empty() && RESERVED.contains(&stem) {
+                die!("Error: {src:?} - \"{stem}\" is reserved for generated pages, rename the directory or set a slug");
+            }
+            let name = format!("{dir}{stem}.html");
+            article.assets = bundle.then(|| format!("{dir}{stem}/"));
+            (src, partial, hash, article, name)
+        })
+        .collect::<Vec<_>>();
+
+    let mut names = std::collections::HashMap::new();
+    for (src, _, _, _, name) in &loaded {
+        if name == "index.html" || cfg.search && name == "search.html" {
+            die!("Error: {src:?} - \"{name}\" is reserved for a generated page, rename the file or set a slug");
+        }
+        if let Some(other) = names.insert(name.as_str(), src) {
+            die!("Error: {other:?} and {src:?} both generate \"{name}\", rename a file or set a slug");
+        }
+    }
 
+    let (articles, linked): (Vec<_>, Vec<_>) = loaded
+        .into_par_iter()
+        .filter_map(|(src, partial, hash, article, name)| {
             let path = cfg.output.join(&name);
-            match article.body() {
-                Some(body) => write(&path, ArticleTemplate {
-                    sitename: &cfg.sitename,
-                    title:    article.name(),
-                    head:     &head,
-                    header:   &header,
-                    footer:   &footer,
-                    ts:       article.ts,
-                    content:  markdown::Markdown(body),
-                }),
+            if !cfg.drafts && (article.draft || article.ts > now) {
+                println!("Info: unpublished {path:?}");
+                unpublished.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                return None;
+            }
+
+            let root = "../".repeat(name.matches('/').count());
+            let base = article.assets.as_deref().map_or("", |v| &v[name.rfind('/').map_or(0, |i| i + 1)..]);
+            if let Some(assets) = &article.assets && let Some(dir) = src.parent() {
+                copy_dir(dir, &cfg.output.join(assets), &previous, &|v| v == src);
+            }
+            let options = options.with(&article.markdown);
+            let document = article.body().map(|v| markdown::Document::new(v, options.extensions, base));
+            let page = document.as_ref().map(|v| links::Page::new(src.clone(), name.clone(), &article, v));
+            let warnings = document.as_ref().map(|v| [v.footnotes(), v.math(), v.ids()].concat());
+            for (warning, offset) in warnings.unwrap_or_default() {
+                println!("Warning: {}:{} - {warning}", src.display(), article.line(offset));
+            }
+            // INFO Dimensions and variants of images are part of the page, it's regenerated when they change
+            let links = document.as_ref().map(markdown::Document::images).unwrap_or_default();
+            let images = images::Images::new(&cfg.output, &name, links, |v| GENERATED.contains(v));
+            let hash = manifest::hash(&[&hash.to_le_bytes(), format!("{images:?}").as_bytes()]);
+            match &document {
+                Some(_) if previous.get(&path) == Some(hash) && path.is_file() => {
+                    unchanged.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    GENERATED.insert(path, hash);
+                },
+                Some(document) => {
+                    if let Some(dir) = path.parent() {
+                        std::fs::create_dir_all(dir).unwrap_or_else(|e| die!("Error: can't open output directory {dir:?} for {src:?} - {e}"));
+                    }
+                    write(&path, "article.html", ArticleTemplate {
+                        site:     &site,
+                        title:    article.name(),
+                        meta:     &article,
+                        head:     &head,
+                        header:   &header,
+                        footer:   &footer,
+                        root:     if root.is_empty() { "./" } else { &root },
+                        ts:       article.ts,
+                        toc:      article.toc.then(|| document.toc()),
+                        content:  markdown::Markdown { document: std::borrow::Cow::Borrowed(document), options, images: Some(&images) },
+                    });
+                    GENERATED.insert(path, hash);
+                },
+                None if partial => if let Some(hash) = previous.get(&path) {
+                    GENERATED.insert(path, hash);
+                },
                 None => println!("Info: ignored {path:?}"),
             }
 
-            (article, name)
+            Some(((article, name), page))
         })
-        .collect::<Vec<_>>();
+        .unzip();
 
-    if articles.len() == 0 {
+    if articles.is_empty() {
         die!("Error: no articles found");
     }
 
     let mut sorted = articles.iter().collect::<Vec<_>>();
     sorted.sort_unstable_by_key(|v| std::cmp::Reverse(v.0.ts));
 
-    write(&cfg.output.join("index.html"), IndexTemplate {
-        sitename: &cfg.sitename,
-        head:     &head, 
-        header:   &header,
-        footer:   &footer,
-        articles: sorted.as_slice(),
-        intro:    markdown::Markdown(&intro),
-    });
+    let per_page = cfg.per_page.map_or(sorted.len(), std::num::NonZeroUsize::get);
+    let pages = sorted.len().div_ceil(per_page);
+    if pages > 1 {
+        let dir = cfg.output.join("page");
+        std::fs::create_dir_all(&dir).unwrap_or_else(|e| die!("Error: can't open output directory {dir:?} - {e}"));
+    }
+
+    let page_href = |from: usize, to: usize| match (from, to) {
+        (_, 1) => "../index.html".to_string(),
+        (1, n) => format!("./page/{n}.html"),
+        (_, n) => format!("./{n}.html"),
+    };
+
+    for (i, articles) in sorted.chunks(per_page).enumerate() {
+        let page = i + 1;
+        let path = match page {
+            1 => cfg.output.join("index.html"),
+            n => cfg.output.join("page").join(format!("{n}.html")),
+        };
+
+        write(&path, "index.html", IndexTemplate {
+            site:     &site,
+            head:     &head,
+            header:   &header,
+            footer:   &footer,
+            root:     if page == 1 { "./" } else { "../" },
+            articles,
+            intro:    (page == 1).then(|| markdown::Markdown::new(&intro, options, "")),
+            page,
+            pages,
+            prev:     (page > 1).then(|| page_href(page, page - 1)),
+            next:     (page < pages).then(|| page_href(page, page + 1)),
+        });
+    }
+
+    let taxonomies: [(&str, &str, taxonomy::Selector); 2] = [
+        ("tags",       "Tags",       |a| &a.tags),
+        ("categories", "Categories", |a| &a.categories),
+    ];
+
+    for (dir, title, terms) in taxonomies {
+        let terms = taxonomy::collect(&sorted, terms);
+        if terms.is_empty() {
+            continue;
+        }
+
+        let dir = cfg.output.join(dir);
+        std::fs::create_dir_all(&dir).unwrap_or_else(|e| die!("Error: can't open output directory {dir:?} - {e}"));
+
+        terms.par_iter().for_each(|term| write(&dir.join(format!("{}.html", term.slug)), "term.html", TermTemplate {
+            site:     &site,
+            title,
+            head:     &head,
+            header:   &header,
+            footer:   &footer,
+            root:     "../",
+            term,
+        }));
+
+        write(&dir.join("index.html"), "taxonomy.html", TaxonomyTemplate {
+            site:     &site,
+            title,
+            head:     &head,
+            header:   &header,
+            footer:   &footer,
+            terms:    &terms,
+        });
+    }
+
+    match site.base_url.as_deref() {
+        Some(base_url) => {
+            let published = sorted.iter().filter(|v| v.0.body().is_some()).copied().collect::<Vec<_>>();
+            let entries = &published[..published.len().min(cfg.feed_limit)];
+            let updated = published.iter().map(|v| v.0.modified()).max().unwrap_or(article::Datetime(0));
+            let feed_options = markdown::Options { classes: cfg.feed_highlight_classes, extensions: options.extensions, ..markdown::Options::default() };
+
+            write(&cfg.output.join("feed.xml"), "atom.xml", AtomTemplate {
+                site:     &site,
+                base_url,
+                options:  feed_options,
+                updated,
+                articles: entries,
+            });
+            write(&cfg.output.join("rss.xml"), "rss.xml", RssTemplate {
+                site:     &site,
+                base_url,
+                options:  feed_options,
+                updated,
+                articles: entries,
+            });
+            write(&cfg.output.join("sitemap.xml"), "sitemap.xml", SitemapTemplate {
+                base_url,
+                updated,
+                articles: &published,
+            });
+            write_file(&cfg.output.join("robots.txt"), format!("User-agent: *\nAllow: /\n\nSitemap: {base_url}/sitemap.xml\n").as_bytes());
+        },
+        None => println!("Warning: \"--base-url\" isn't set, feeds, sitemap and robots.txt skipped"),
+    }
+
+    if cfg.search {
+        // INFO Articles loaded without a body because of "--files" are indexed by their front matter
+        write_file(&cfg.output.join(search::INDEX), search::index(&sorted, options.extensions).as_bytes());
+        write_file(&cfg.output.join(search::SCRIPT), search::SCRIPT_SOURCE.as_bytes());
+        write(&cfg.output.join("search.html"), "search.html", SearchTemplate {
+            site:     &site,
+            head:     &head,
+            header:   &header,
+            footer:   &footer,
+        });
+    }
+
+    let linked = linked.into_iter().flatten().collect::<Vec<_>>();
+    let broken = links::check(&linked, |v| GENERATED.contains(&cfg.output.join(v)));
+    broken.iter().for_each(|v| println!("Warning: {v}"));
+
+    let pruned = GENERATED.prune(&previous, &cfg.output);
+    GENERATED.save(&cfg.output).unwrap_or_else(|e| die!("Error: can't write {:?} - {e}", cfg.output.join(manifest::FILE)));
 
     println!(
-        "Done in {:.2} seconds: {} indexed, {} parsed, index generated",
+        "Done in {:.2} seconds: {} indexed, {} parsed, {} unchanged, {} unpublished, {} pruned, {} index page(s) generated",
         time.elapsed().as_secs_f64(),
         articles.len(),
         articles.iter().filter(|v| v.0.body().is_some()).count(),
+        unchanged.into_inner(),
+        unpublished.into_inner(),
+        pruned,
+        pages,
     );
+
+    if cfg.strict && !broken.is_empty() {
+        die!("Error: {} broken link(s)", broken.len());
+    }
 }
 
-fn load_asset(dir: &PathBuf, path: &str, html: bool) -> String {
+fn parse_datetime(s: &str) -> Result<article::Datetime, String> {
+    article::Datetime::parse(s).ok_or_else(|| format!("expected unix seconds or \"2024-01-05T10:00:00Z\", got {s:?}"))
+}
+
+fn load_asset(dir: &Path, path: &str, html: bool) -> String {
     fn load_html(src: PathBuf) -> Result<String, std::io::Error> {
         let file = std::fs::File::open(src)?;
         let mut s = String::with_capacity(file.metadata().map(|v| v.len() as usize).unwrap_or_default());
@@ -150,50 +595,202 @@ fn load_asset(dir: &PathBuf, path: &str, html: bool) -> String {
     }
 }
 
-fn load_dir(path: &PathBuf) -> Result<Vec<(String, PathBuf)>, std::io::Error> {
-    let mut result = Vec::new();
+/// Loads runtime grammars and query overrides, returns a hash of the loaded files
+fn load_highlights(dir: &Path) -> u64 {
+    if !dir.is_dir() {
+        return 0;
+    }
+
+    let count = markdown::LANG_DB.load(dir).unwrap_or_else(|e| die!("Error: can't load highlights {e}"));
+    println!("Info: {count} language(s) loaded from {dir:?}");
+    hash_dir(dir)
+}
+
+/// Loads runtime template overrides, returns a hash of the directory
+fn load_templates(dir: &Path) -> u64 {
+    if !dir.is_dir() {
+        return 0;
+    }
+
+    let names = template::load(dir).unwrap_or_else(|e| die!("Error: can't load template {e}"));
+    names.iter().for_each(|name| println!("Info: \"{name}\" overridden by {:?}", dir.join(name)));
+    hash_dir(dir)
+}
 
-    for entry in std::fs::read_dir(path)? {
-        let src = entry?.path();
-        if 
-            src.is_file() &&
-            src.extension().map_or(false, |ext| ext == "md") &&
-            let Some(dst) = src.file_stem() &&
-            let Some(dst) = dst.to_str() &&
-            dst.len() > 0 &&
-            dst.chars().all(|c| matches!(c, 'a'..='z' | '0'..='9' | '_')) &&
-            dst != "index"
-        {
-            result.push((format!("{dst}.html"), src));
-        } else {
-            println!("Warning: {src:?} - isn't \"ascii_alphanumeric_lowercase.md\", ignored");
+/// Hash of paths and contents of all files in `dir`, recursively
+fn hash_dir(dir: &Path) -> u64 {
+    fn visit(path: &Path, parts: &mut Vec<Vec<u8>>) {
+        let mut entries = std::fs::read_dir(path).into_iter().flatten().flatten().map(|v| v.path()).collect::<Vec<_>>();
+        entries.sort_unstable();
+        for entry in entries {
+            match entry.is_dir() {
+                true => visit(&entry, parts),
+                false => {
+                    parts.push(entry.to_string_lossy().into_owned().into_bytes());
+                    parts.push(std::fs::read(&entry).unwrap_or_else(|e| die!("Error: can't read {entry:?} - {e}")));
+                },
+            }
         }
     }
 
+    let mut parts = Vec::new();
+    visit(dir, &mut parts);
+    manifest::hash(&parts.iter().map(Vec::as_slice).collect::<Vec<_>>())
+}
+
+/// Markdown files of `path` and its subdirectories as (output directory, e.g. "2024/notes/", file stem, source, bundle),
+/// names are slugified, so "Notes/My Post.md" becomes "notes/" and "my-post".
+/// A subdirectory with "index.md" is a page bundle, "my_post/index.md" becomes "my-post" with other files as its assets
+fn load_dir(path: &Path) -> Result<Vec<(String, String, PathBuf, bool)>, std::io::Error> {
+    fn visit(path: &Path, dir: &str, result: &mut Vec<(String, String, PathBuf, bool)>) -> Result<(), std::io::Error> {
+        let mut entries = std::fs::read_dir(path)?.map(|v| v.map(|v| v.path())).collect::<Result<Vec<_>, _>>()?;
+        entries.sort_unstable();
+
+        for src in entries {
+            let Some(name) = src.file_name().and_then(|v| v.to_str()) else {
+                println!("Warning: {src:?} - isn't valid UTF-8, ignored");
+                continue;
+            };
+            if name.starts_with('.') {
+                continue;
+            }
+
+            let stem = src.file_stem().and_then(|v| v.to_str()).map(slug::slugify).unwrap_or_default();
+            if src.is_dir() {
+                match slug::slugify(name) {
+                    s if s.is_empty() => println!("Warning: {src:?} - directory name has no letters or digits, ignored"),
+                    s if dir.is_empty() && RESERVED.contains(&s.as_str()) => println!("Warning: {src:?} - \"{s}\" is reserved for generated pages, ignored"),
+                    s if src.join("index.md").is_file() => result.push((dir.to_string(), s, src.join("index.md"), true)),
+                    s => visit(&src, &format!("{dir}{s}/"), result)?,
+                }
+            } else if src.extension().is_none_or(|ext| ext != "md") {
+                println!("Warning: {src:?} - isn't a markdown file, ignored");
+            } else if stem.is_empty() {
+                println!("Warning: {src:?} - file name has no letters or digits, ignored");
+            } else {
+                result.push((dir.to_string(), stem, src, false));
+            }
+        }
+
+        Ok(())
+    }
+
+    let mut result = Vec::new();
+    visit(path, "", &mut result)?;
     Ok(result)
 }
 
-fn load_article(path: &PathBuf, filter: Option<&[PathBuf]>) -> Result<String, std::io::Error> {
-    let file = std::fs::File::open(&path)?;
-    let mut s = match filter.map_or(false, |v| v.iter().any(|f| f == path)) {
+fn load_article(path: &PathBuf, partial: bool) -> Result<String, std::io::Error> {
+    let file = std::fs::File::open(path)?;
+    let mut s = match partial {
         true => {
-            let mut line = String::with_capacity(512);
-            std::io::BufReader::new(file).read_line(&mut line)?;
-            Ok(line)
+            let mut reader = std::io::BufReader::new(file);
+            let mut header = String::with_capacity(512);
+            reader.read_line(&mut header)?;
+            if let Some(format) = front_matter::Format::from_delimiter(&header) {
+                let mut line = String::with_capacity(128);
+                while reader.read_line(&mut line)? > 0 {
+                    header.push_str(&line);
+                    if line.trim_end() == format.delimiter() {
+                        break;
+                    }
+                    line.clear();
+                }
+            }
+            Ok(header)
         },
-        false => std::fs::read_to_string(&path),
+        false => std::fs::read_to_string(path),
     }?;
     s.retain(|c| c != '\r');
     Ok(s)
 }
 
-fn write<T: askama::Template>(dst: &PathBuf, t: T) {
+/// "templates" next to the content directory
+fn templates_dir(content: &Path) -> PathBuf {
+    content.parent().unwrap_or(Path::new("")).join("templates")
+}
+
+/// "static" next to the content directory, copied to the output unchanged before pages are generated
+fn static_dir(content: &Path) -> PathBuf {
+    content.parent().unwrap_or(Path::new("")).join("static")
+}
+
+/// Copies files of `src` recursively into `dst` except `skip` ones, unchanged files aren't rewritten
+fn copy_dir(src: &Path, dst: &Path, previous: &manifest::Manifest, skip: &dyn Fn(&Path) -> bool) {
+    let mut entries = std::fs::read_dir(src)
+        .and_then(|v| v.map(|v| v.map(|v| v.path())).collect::<Result<Vec<_>, _>>())
+        .unwrap_or_else(|e| die!("Error: can't read {src:?} - {e}"));
+    entries.sort_unstable();
+
+    for entry in entries {
+        let Some(name) = entry.file_name() else {
+            continue;
+        };
+        if name.to_string_lossy().starts_with('.') || skip(&entry) {
+            continue;
+        }
+
+        let path = dst.join(name);
+        if entry.is_dir() {
+            copy_dir(&entry, &path, previous, skip);
+            continue;
+        }
+
+        let data = std::fs::read(&entry).unwrap_or_else(|e| die!("Error: can't read {entry:?} - {e}"));
+        let hash = manifest::hash(&[&data]);
+        if previous.get(&path) != Some(hash) || !path.is_file() {
+            std::fs::create_dir_all(dst).unwrap_or_else(|e| die!("Error: can't open output directory {dst:?} - {e}"));
+            std::fs::write(&path, &data).unwrap_or_else(|e| die!("Error: can't write to {path:?} - {e}"));
+            println!("Info: copied {entry:?} to {path:?}");
+        }
+        GENERATED.insert(path, hash);
+
+        #[cfg(feature = "images")]
+        if let Ok(size) = imagesize::blob_size(&data) {
+            // INFO Source files like "a.webp" next to "a.png" aren't overwritten by variants
+            let (variants, sources) = images::variants(&name.to_string_lossy(), size.width as u32).into_iter()
+                .partition::<Vec<_>, _>(|(v, _, _)| !entry.with_file_name(v).exists());
+            for (v, _, _) in sources {
+                println!("Warning: {:?} isn't generated from {entry:?}, the file exists in the content", entry.with_file_name(v));
+            }
+            let variants = variants.into_iter().map(|(v, width, format)| (dst.join(v), width, format)).collect::<Vec<_>>();
+            if variants.iter().any(|(v, _, _)| previous.get(v) != Some(hash) || !v.is_file()) {
+                match images::generate(&data, &variants) {
+                    Ok(_) => println!("Info: generated {} variant(s) of {entry:?}", variants.len()),
+                    Err(e) => println!("Warning: can't generate variants of {entry:?} - {e}"),
+                }
+            }
+            variants.into_iter().filter(|(v, _, _)| v.is_file()).for_each(|(v, _, _)| GENERATED.insert(v, hash));
+        }
+    }
+}
+
+/// Renders the runtime override of `name` if there is one, the built-in template otherwise
+fn write<T: askama::Template + serde::Serialize>(dst: &PathBuf, name: &str, t: T) {
+    if let Some(result) = template::render(name, &t) {
+        let data = result.unwrap_or_else(|e| die!("Error: can't render {dst:?} - {e}"));
+        return write_file(dst, data.as_bytes());
+    }
+
     match inner(dst, t) {
-        Ok(_) => println!("Info: successfully generated {dst:?}"),
+        Ok(_) => {
+            println!("Info: successfully generated {dst:?}");
+            GENERATED.insert(dst.clone(), 0);
+        },
         Err(e) => die!("Error: can't write to {dst:?} - {e}"),
     }
 
     fn inner<T: askama::Template>(dst: &PathBuf, t: T) -> Result<(), std::io::Error> {
-        askama::Template::write_into(&t, &mut std::io::BufWriter::new(std::fs::File::create(&dst)?))
+        askama::Template::write_into(&t, &mut std::io::BufWriter::new(std::fs::File::create(dst)?))
+    }
+}
+
+fn write_file(dst: &Path, data: &[u8]) {
+    match std::fs::write(dst, data) {
+        Ok(_) => {
+            println!("Info: successfully generated {dst:?}");
+            GENERATED.insert(dst.to_path_buf(), 0);
+        },
+        Err(e) => die!("Error: can't write to {dst:?} - {e}"),
     }
 }
\ No newline at end of file
@@ -0,0 +1,170 @@
+use std::path::{ Path, PathBuf };
+
+pub const FILE: &str = ".manifest";
+
+/// Built-in templates, scripts and highlight queries, they change outputs without a version bump
+const BUILTIN: u64 = hash(&[
+    include_bytes!("../templates/article.html"),
+    include_bytes!("../templates/atom.xml"),
+    include_bytes!("../templates/index.html"),
+    include_bytes!("../templates/rss.xml"),
+    include_bytes!("../templates/search.html"),
+    include_bytes!("../templates/sitemap.xml"),
+    include_bytes!("../templates/taxonomy.html"),
+    include_bytes!("../templates/term.html"),
+    include_bytes!("search.js"),
+    include_bytes!("../highlights/c_sharp.scm"),
+    include_bytes!("../highlights/julia.scm"),
+    include_bytes!("../highlights/pascal.scm"),
+    include_bytes!("../highlights/pwsh.scm"),
+    include_bytes!("../highlights/rust_injections.scm"),
+]);
+
+/// First line of the manifest, hashes of another build aren't reused
+const HEADER: &str = const_format::formatcp!("{} {} {:x}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), BUILTIN);
+
+/// FNV-1a, stable between runs and platforms unlike `std::hash::DefaultHasher`
+pub const fn hash(parts: &[&[u8]]) -> u64 {
+    let mut h: u64 = 0xcbf29ce484222325;
+    let mut i = 0;
+    while i < parts.len() {
+        let len = (parts[i].len() as u64).to_le_bytes();
+        let mut j = 0;
+        while j < parts[i].len() + len.len() {
+            let b = if j < parts[i].len() { parts[i][j] } else { len[j - parts[i].len()] };
+            h ^= b as u64;
+            h = h.wrapping_mul(0x100000001b3);
+            j += 1;
+        }
+        i += 1;
+    }
+    h
+}
+
+/// Generated files with hashes of their inputs, zero hash means "always regenerated"
+pub struct Manifest(std::sync::Mutex<std::collections::BTreeMap<PathBuf, u64>>);
+
+impl Manifest {
+    pub const fn new() -> Self {
+        Self(std::sync::Mutex::new(std::collections::BTreeMap::new()))
+    }
+
+    pub fn load(output: &Path) -> Self {
+        let result = Self::new();
+        let Ok(s) = std::fs::read_to_string(output.join(FILE)) else {
+            return result;
+        };
+
+        // INFO Paths of another build are kept with zero hashes, so they're regenerated and still pruned
+        let mut lines = s.lines();
+        let current = lines.next() == Some(HEADER);
+        if !current {
+            println!("Warning: {:?} is from another build, full rebuild", output.join(FILE));
+        }
+
+        let mut map = result.0.lock().unwrap();
+        for line in lines {
+            if let Some((hash, path)) = line.split_once(' ') && let Ok(hash) = u64::from_str_radix(hash, 16) {
+                map.insert(output.join(path), if current { hash } else { 0 });
+            }
+        }
+        drop(map);
+
+        result
+    }
+
+    pub fn get(&self, path: &Path) -> Option<u64> {
+        self.0.lock().unwrap().get(path).copied()
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.0.lock().unwrap().contains_key(path)
+    }
+
+    pub fn insert(&self, path: PathBuf, hash: u64) {
+        self.0.lock().unwrap().insert(path, hash);
+    }
+
+    pub fn save(&self, output: &Path) -> Result<(), std::io::Error> {
+        let mut s = format!("{HEADER}\n");
+        for (path, hash) in self.0.lock().unwrap().iter() {
+            if let Ok(path) = path.strip_prefix(output) && let Some(path) = path.to_str() {
+                s.push_str(&format!("{hash:016x} {}\n", path.replace('\\', "/")));
+            }
+        }
+        std::fs::write(output.join(FILE), s)
+    }
+
+    /// Removes files generated by the previous build but not by the current one,
+    /// and directories under `output` left empty by that
+    pub fn prune(&self, previous: &Manifest, output: &Path) -> usize {
+        let current = self.0.lock().unwrap();
+        let previous = previous.0.lock().unwrap();
+        let mut count = 0;
+
+        for path in previous.keys().filter(|v| !current.contains_key(*v)) {
+            match std::fs::remove_file(path) {
+                Ok(_) => {
+                    println!("Info: pruned {path:?}");
+                    count += 1;
+                    for dir in path.ancestors().skip(1).take_while(|v| v.starts_with(output) && *v != output) {
+                        if std::fs::remove_dir(dir).is_err() {
+                            break;
+                        }
+                    }
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+                Err(e) => println!("Warning: can't prune {path:?} - {e}"),
+            }
+        }
+
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash() {
+        assert_eq!(hash(&[]), 0xcbf29ce484222325);
+        assert_eq!(hash(&[b"ab", b"c"]), hash(&[b"ab", b"c"]));
+        assert_ne!(hash(&[b"ab", b"c"]), hash(&[b"a", b"bc"]));
+        assert_ne!(hash(&[b"a"]), hash(&[b"b"]));
+    }
+
+    #[test]
+    fn test_manifest() {
+        let output = crate::test_utils::TempDir::new("manifest");
+        std::fs::create_dir_all(output.join("tags/a/b")).unwrap();
+        ["kept.html", "stale.html", "tags/a/b/stale.html"].iter().for_each(|v| std::fs::write(output.join(v), "").unwrap());
+
+        let previous = Manifest::new();
+        previous.insert(output.join("kept.html"), 1);
+        previous.insert(output.join("stale.html"), 2);
+        previous.insert(output.join("tags/a/b/stale.html"), 0);
+        previous.insert(output.join("missing.html"), 3);
+        previous.save(&output).unwrap();
+
+        let loaded = Manifest::load(&output);
+        assert_eq!(loaded.get(&output.join("kept.html")), Some(1));
+        assert_eq!(loaded.get(&output.join("tags/a/b/stale.html")), Some(0));
+
+        let current = Manifest::new();
+        current.insert(output.join("kept.html"), 1);
+        assert_eq!(current.prune(&loaded, &output), 2);
+        assert!(output.join("kept.html").is_file());
+        assert!(!output.join("stale.html").exists());
+        assert!(!output.join("tags").exists());
+        assert!(output.is_dir());
+
+        // Another build regenerates every file and prunes the ones it doesn't generate
+        let s = std::fs::read_to_string(output.join(FILE)).unwrap();
+        std::fs::write(output.join(FILE), s.replacen(HEADER, "blog_generator 0.0.0 0", 1)).unwrap();
+        let loaded = Manifest::load(&output);
+        assert_eq!(loaded.get(&output.join("kept.html")), Some(0));
+        assert_eq!(Manifest::new().prune(&loaded, &output), 1);
+        assert!(!output.join("kept.html").exists());
+    }
+}
\ No newline at end of file
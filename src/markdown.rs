@@ -2,17 +2,529 @@ use pulldown_cmark::{ Alignment, BlockQuoteKind, CodeBlockKind, Event, LinkType,
 use pulldown_cmark_escape::{ escape_href, escape_html, escape_html_body_text, FmtWriter, StrWrite };
 use crate::tree_sitter_html::*;
 
-static LANG_DB: crate::tree_sitter_html::LangDb = crate::tree_sitter_html::LangDb::new();
+pub static LANG_DB: crate::tree_sitter_html::LangDb = crate::tree_sitter_html::LangDb::new();
 
-pub struct Markdown<'a>(pub &'a str);
+/// pulldown-cmark extensions, enabled for the site with "--markdown"
+/// and switched per article with the "markdown" front matter key, e.g. "[math, no-tables]"
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Extension {
+    Tables,
+    Footnotes,
+    /// Footnotes with the original syntax, where definitions can't be nested
+    OldFootnotes,
+    Strikethrough,
+    Tasklists,
+    SmartPunctuation,
+    /// "# Heading {#id .class key=value}"
+    HeadingAttributes,
+    /// YAML "---" and TOML "+++" metadata blocks, skipped in the output
+    MetadataBlocks,
+    /// TeX between "$" or "$$" rendered to MathML, off by default as it changes existing text with two "$"
+    Math,
+    /// GitHub alerts like "> [!NOTE]"
+    Gfm,
+    DefinitionList,
+    Superscript,
+    Subscript,
+    Wikilinks,
+}
+
+impl Extension {
+    pub const DEFAULT: &[Extension] = &[Extension::Tables, Extension::Strikethrough, Extension::MetadataBlocks];
+
+    fn flags(self) -> pulldown_cmark::Options {
+        use pulldown_cmark::Options as O;
+        match self {
+            Self::Tables            => O::ENABLE_TABLES,
+            Self::Footnotes         => O::ENABLE_FOOTNOTES,
+            Self::OldFootnotes      => O::ENABLE_OLD_FOOTNOTES,
+            Self::Strikethrough     => O::ENABLE_STRIKETHROUGH,
+            Self::Tasklists         => O::ENABLE_TASKLISTS,
+            Self::SmartPunctuation  => O::ENABLE_SMART_PUNCTUATION,
+            Self::HeadingAttributes => O::ENABLE_HEADING_ATTRIBUTES,
+            Self::MetadataBlocks    => O::ENABLE_YAML_STYLE_METADATA_BLOCKS | O::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS,
+            Self::Math              => O::ENABLE_MATH,
+            Self::Gfm               => O::ENABLE_GFM,
+            Self::DefinitionList    => O::ENABLE_DEFINITION_LIST,
+            Self::Superscript       => O::ENABLE_SUPERSCRIPT,
+            Self::Subscript         => O::ENABLE_SUBSCRIPT,
+            Self::Wikilinks         => O::ENABLE_WIKILINKS,
+        }
+    }
+}
+
+/// Set of enabled extensions, (de)serialized as a list of `Extension` names
+#[derive(Clone, Copy, PartialEq)]
+pub struct Extensions(pulldown_cmark::Options);
+
+impl Extensions {
+    pub fn new(list: &[Extension]) -> Self {
+        Self(list.iter().fold(pulldown_cmark::Options::empty(), |acc, v| acc | v.flags()))
+    }
+
+    /// Applies per-article switches, `(extension, false)` disables it
+    pub fn with(mut self, switches: &[(Extension, bool)]) -> Self {
+        for &(extension, enabled) in switches {
+            match (extension, enabled) {
+                (extension, true) => self.0.insert(extension.flags()),
+                // INFO old footnotes include the footnotes flag, which stays as is
+                (Extension::OldFootnotes, false) => self.0.remove(Extension::OldFootnotes.flags().difference(Extension::Footnotes.flags())),
+                (extension, false) => self.0.remove(extension.flags()),
+            }
+        }
+        self
+    }
+
+    fn list(self) -> Vec<Extension> {
+        <Extension as clap::ValueEnum>::value_variants().iter().copied().filter(|v| self.0.contains(v.flags())).collect()
+    }
+}
+
+impl Default for Extensions {
+    fn default() -> Self {
+        Self::new(Extension::DEFAULT)
+    }
+}
+
+impl std::fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.list()).finish()
+    }
+}
+
+impl serde::Serialize for Extensions {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&self.list(), s)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Extensions {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        Vec::<Extension>::deserialize(d).map(|v| Self::new(&v))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Options {
+    /// Append a self-link "#" to every heading
+    pub anchors:    bool,
+    /// Class names of highlighted code spans
+    pub classes:    Classes,
+    pub extensions: Extensions,
+}
+
+impl Options {
+    /// Site options with extensions switched by an article
+    pub fn with(self, switches: &[(Extension, bool)]) -> Self {
+        Self { extensions: self.extensions.with(switches), ..self }
+    }
+}
+
+/// Events of a text parsed once with heading ids, prefixed image links and figures,
+/// shared by rendering, the table of contents and checks of an article
+#[derive(Clone)]
+pub struct Document<'a> {
+    events:     Vec<Event<'a>>,
+    /// Byte offsets of `events` in the text
+    offsets:    Vec<usize>,
+    headings:   Vec<Heading<'a>>,
+    extensions: Extensions,
+}
+
+impl<'a> Document<'a> {
+    /// `base` prefixes relative image links, e.g. "my-post/" for a page bundle
+    pub fn new(s: &'a str, extensions: Extensions, base: &str) -> Self {
+        let (mut events, offsets): (Vec<_>, Vec<_>) = pulldown_cmark::Parser::new_ext(s, extensions.0)
+            .into_offset_iter()
+            .map(|(event, range)| (event, range.start))
+            .unzip();
+        let headings = headings(&mut events);
+        rebase(&mut events, base);
+        figures(&mut events);
+        Self { events, offsets, headings, extensions }
+    }
+
+    /// Rendered image links
+    pub fn images(&self) -> Vec<String> {
+        self.events.iter().filter_map(|event| match event {
+            Event::Start(Tag::Image { dest_url, .. }) => Some(dest_url.to_string()),
+            _ => None,
+        }).collect()
+    }
+
+    /// Rendered link and image destinations with their byte offsets, and heading ids
+    pub fn links(&self) -> (Vec<(String, usize)>, Vec<String>) {
+        let links = self.events.iter().zip(&self.offsets).filter_map(|(event, offset)| match event {
+            Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. }) => Some((dest_url.to_string(), *offset)),
+            _ => None,
+        });
+        (links.collect(), self.headings.iter().map(|v| v.id.to_string()).collect())
+    }
+
+    /// Problems of footnotes with their offsets, undefined references and unreferenced definitions
+    pub fn footnotes(&self) -> Vec<(String, usize)> {
+        let events = &self.events;
+        let mut defined = Vec::new();
+        let mut referenced = Vec::new();
+        for (i, (event, offset)) in events.iter().zip(&self.offsets).enumerate() {
+            match event {
+                Event::Start(Tag::FootnoteDefinition(name)) => defined.push((name.as_ref(), *offset)),
+                Event::FootnoteReference(name) => referenced.push((name.as_ref(), *offset)),
+                // INFO New style footnotes keep undefined references as text "[", "^name", "]"
+                Event::Text(name) if name.starts_with('^') && self.extensions.0.contains(pulldown_cmark::Options::ENABLE_FOOTNOTES) => {
+                    if let Some(Event::Text(open)) = i.checked_sub(1).map(|i| &events[i]) && open.as_ref() == "["
+                        && let Some(Event::Text(close)) = events.get(i + 1) && close.starts_with(']') {
+                        referenced.push((&name[1..], offset - 1));
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        let mut result = Vec::new();
+        for (name, offset) in &referenced {
+            if !defined.iter().any(|v| v.0 == *name) {
+                result.push((format!("undefined footnote {name:?}"), *offset));
+            }
+        }
+        for (name, offset) in &defined {
+            if !referenced.iter().any(|v| v.0 == *name) {
+                result.push((format!("unreferenced footnote {name:?}"), *offset));
+            }
+        }
+        result.sort_by_key(|v| v.1);
+        result
+    }
+
+    /// Warnings about explicit heading ids like "# A {#a}" used more than once with byte offsets,
+    /// generated ids never repeat others, so only explicit ones can
+    pub fn ids(&self) -> Vec<(String, usize)> {
+        let mut used = std::collections::HashSet::new();
+        self.events.iter().zip(&self.offsets)
+            .filter_map(|(event, offset)| match event {
+                Event::Start(Tag::Heading { id: Some(id), .. }) if !used.insert(id) => Some((format!("duplicate heading id {:?}", id.as_ref()), *offset)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Warnings about math that can't be rendered to MathML with byte offsets, it's kept as TeX
+    pub fn math(&self) -> Vec<(String, usize)> {
+        self.events.iter().zip(&self.offsets)
+            .filter_map(|(event, offset)| match event {
+                Event::InlineMath(text) => Some((text, false, *offset)),
+                Event::DisplayMath(text) => Some((text, true, *offset)),
+                _ => None,
+            })
+            .filter_map(|(text, display, offset)| {
+                crate::mathml::render(text, display).err().map(|e| (format!("can't render math {:?} - {e}, TeX is kept", text.as_ref()), offset))
+            })
+            .collect()
+    }
+
+    pub fn toc(&self) -> Toc<'_> {
+        Toc(&self.headings)
+    }
+}
+
+/// Renders to HTML on display
+pub struct Markdown<'a> {
+    pub document: std::borrow::Cow<'a, Document<'a>>,
+    pub options:  Options,
+    /// Local images of the page, images are lazy loaded when they're set
+    pub images:   Option<&'a crate::images::Images>,
+}
+
+impl<'a> Markdown<'a> {
+    /// `base` prefixes relative image links, e.g. "my-post/" for a page bundle
+    pub fn new(text: &'a str, options: Options, base: &str) -> Self {
+        Self { document: std::borrow::Cow::Owned(Document::new(text, options.extensions, base)), options, images: None }
+    }
+}
 
 impl<'a> std::fmt::Display for Markdown<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let parser = pulldown_cmark::Parser::new_ext(self.0, pulldown_cmark::Options::ENABLE_TABLES | pulldown_cmark::Options::ENABLE_STRIKETHROUGH);
-        HtmlWriter::new(self.0, parser, FmtWriter(f)).run()
+        let mut writer = HtmlWriter::new(self.options, self.document.events.iter().cloned(), FmtWriter(f));
+        writer.images = self.images;
+        writer.run()
+    }
+}
+
+/// Rendered HTML, marked as safe in templates
+impl serde::Serialize for Markdown<'_> {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match minijinja::value::serializing_for_value() {
+            true => minijinja::Value::from_safe_string(self.to_string()).serialize(s),
+            false => s.collect_str(self),
+        }
     }
 }
 
+/// Text without markup, blocks are separated by spaces
+pub fn text(s: &str, extensions: Extensions) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut iter = pulldown_cmark::Parser::new_ext(s, extensions.0);
+    let _ = plain_text::<std::convert::Infallible>(&mut iter, &mut std::collections::HashMap::new(), |v| {
+        result.push_str(v);
+        Ok(())
+    });
+    result
+}
+
+/// Passes text of events to `f` up to the end of the current tag, or to the end of events at the top level,
+/// the same for alt texts of images and for the search index, blocks are separated by spaces
+fn plain_text<'a, E>(
+    iter: &mut impl Iterator<Item = Event<'a>>,
+    numbers: &mut std::collections::HashMap<pulldown_cmark::CowStr<'a>, usize>,
+    mut f: impl FnMut(&str) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut nest = 0;
+    while let Some(event) = iter.next() {
+        match event {
+            Event::Start(Tag::MetadataBlock(_)) => {
+                iter.find(|v| matches!(v, Event::End(TagEnd::MetadataBlock(_))));
+            },
+            Event::Start(_) => nest += 1,
+            Event::End(tag) => {
+                if nest == 0 {
+                    break;
+                }
+                nest -= 1;
+                let inline = matches!(tag, TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough
+                    | TagEnd::Superscript | TagEnd::Subscript | TagEnd::Link | TagEnd::Image);
+                if !inline {
+                    f(" ")?;
+                }
+            },
+            Event::Html(_) | Event::InlineHtml(_) => (),
+            Event::Code(text) | Event::Text(text) => f(&text)?,
+            Event::InlineMath(text) => {
+                f("$")?;
+                f(&text)?;
+                f("$")?;
+            },
+            Event::DisplayMath(text) => {
+                f("$$")?;
+                f(&text)?;
+                f("$$")?;
+            },
+            Event::SoftBreak | Event::HardBreak | Event::Rule => f(" ")?,
+            Event::FootnoteReference(name) => {
+                let len = numbers.len() + 1;
+                let number = *numbers.entry(name).or_insert(len);
+                f(&format!("[{number}]"))?;
+            },
+            Event::TaskListMarker(true)  => f("[x]")?,
+            Event::TaskListMarker(false) => f("[ ]")?,
+        }
+    }
+    Ok(())
+}
+
+/// Nested list of links to the headings, with the same ids as `Markdown` generates
+pub struct Toc<'a>(&'a [Heading<'a>]);
+
+impl<'a> std::fmt::Display for Toc<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let headings = self.0;
+        if headings.is_empty() {
+            return Ok(());
+        }
+
+        let mut w = FmtWriter(f);
+        let mut levels = Vec::new();
+        w.write_str("<nav class=\"toc\">")?;
+        for heading in headings {
+            match levels.last() {
+                Some(&level) if heading.level <= level => {
+                    w.write_str("</li>")?;
+                    while levels.len() > 1 && heading.level < *levels.last().unwrap() {
+                        w.write_str("</ul></li>")?;
+                        levels.pop();
+                    }
+                },
+                _ => {
+                    w.write_str("<ul>")?;
+                    levels.push(heading.level);
+                },
+            }
+            w.write_str("<li><a href=\"#")?;
+            escape_href(&mut w, &heading.id)?;
+            w.write_str("\">")?;
+            escape_html_body_text(&mut w, &heading.text)?;
+            w.write_str("</a>")?;
+        }
+        w.write_str("</li>")?;
+        for _ in 1..levels.len() {
+            w.write_str("</ul></li>")?;
+        }
+        w.write_str("</ul></nav>")
+    }
+}
+
+/// Rendered HTML, marked as safe in templates
+impl serde::Serialize for Toc<'_> {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match minijinja::value::serializing_for_value() {
+            true => minijinja::Value::from_safe_string(self.to_string()).serialize(s),
+            false => s.collect_str(self),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Heading<'a> {
+    level: pulldown_cmark::HeadingLevel,
+    id:    pulldown_cmark::CowStr<'a>,
+    text:  String,
+}
+
+/// Prepends `base` to relative image links, so co-located files of a page bundle resolve
+fn rebase(events: &mut [Event<'_>], base: &str) {
+    if base.is_empty() {
+        return;
+    }
+
+    for event in events {
+        if let Event::Start(Tag::Image { dest_url, .. }) = event && is_relative(dest_url) {
+            *dest_url = format!("{base}{}", dest_url.trim_start_matches("./")).into();
+        }
+    }
+}
+
+/// Paragraphs with only an image with a title become figures with the title as the caption
+fn figures(events: &mut [Event<'_>]) {
+    for i in 0..events.len() {
+        let [Event::Start(Tag::Paragraph), Event::Start(Tag::Image { title, .. }), ..] = &mut events[i..] else {
+            continue;
+        };
+        if title.is_empty() {
+            continue;
+        }
+        let Some(end) = events[i + 2..].iter().position(|v| matches!(v, Event::End(TagEnd::Image))).map(|v| v + i + 2) else {
+            continue;
+        };
+        if !matches!(events.get(end + 1), Some(Event::End(TagEnd::Paragraph))) {
+            continue;
+        }
+
+        let Event::Start(Tag::Image { title, .. }) = &mut events[i + 1] else {
+            continue;
+        };
+        let mut caption = String::from("<figcaption>");
+        escape_html_body_text(&mut caption, title).expect("writing to a String");
+        caption.push_str("</figcaption></figure>");
+        *title = "".into();
+        events[i] = Event::Html("<figure>".into());
+        events[end + 1] = Event::Html(caption.into());
+    }
+}
+
+/// Not empty, without a scheme like "https:" or "data:", not starting with "/", "#" or "?"
+fn is_relative(url: &str) -> bool {
+    let scheme = url.split_once(':').is_some_and(|(v, _)| !v.is_empty() && v.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)));
+    !url.is_empty() && !scheme && !url.starts_with(['/', '#', '?'])
+}
+
+/// Assigns unique slug ids to headings without an explicit "{#id}"
+fn headings<'a>(events: &mut [Event<'a>]) -> Vec<Heading<'a>> {
+    let mut result = Vec::new();
+    // INFO Explicit ids are taken first, so generated ones don't repeat them, duplicates among them are warned by `Document::ids()`
+    let mut used = events.iter()
+        .filter_map(|v| match v {
+            Event::Start(Tag::Heading { id: Some(id), .. }) => Some(id.to_string()),
+            _ => None,
+        })
+        .collect::<std::collections::HashSet<_>>();
+
+    for i in 0..events.len() {
+        let Event::Start(Tag::Heading { level, id, .. }) = &events[i] else {
+            continue;
+        };
+        let level = *level;
+        let explicit = id.clone();
+
+        let mut text = String::new();
+        for event in &events[i + 1..] {
+            match event {
+                Event::End(TagEnd::Heading(_)) => break,
+                Event::Text(s) | Event::Code(s) | Event::InlineMath(s) | Event::DisplayMath(s) => text.push_str(s),
+                Event::SoftBreak | Event::HardBreak => text.push(' '),
+                _ => (),
+            }
+        }
+
+        let id = match explicit {
+            Some(id) => id,
+            None => {
+                let base = match crate::slug::slugify(&text) {
+                    slug if slug.is_empty() => "section".to_string(),
+                    slug => slug,
+                };
+                let id = (0..)
+                    .map(|n| if n == 0 { base.clone() } else { format!("{base}-{n}") })
+                    .find(|v| !used.contains(v))
+                    .unwrap();
+                used.insert(id.clone());
+                pulldown_cmark::CowStr::from(id)
+            },
+        };
+
+        if let Event::Start(Tag::Heading { id: slot, .. }) = &mut events[i] {
+            *slot = Some(id.clone());
+        }
+        result.push(Heading { level, id, text: text.trim().to_string() });
+    }
+
+    result
+}
+
+/// Parses fence info like "rust", "rust,linenos,hl_lines=3-5 7,start=10" or "{.rust linenos hl=3..5}"
+fn code_info(info: &str) -> (Option<String>, Lines) {
+    let info = info.trim();
+    let info = info.strip_prefix('{').and_then(|v| v.strip_suffix('}')).unwrap_or(info);
+
+    let mut lang = None;
+    let mut lines = Lines::default();
+    let mut in_highlight = false;
+
+    for (i, token) in info.split([',', ' ', '\t']).filter(|v| !v.is_empty()).enumerate() {
+        let (key, value) = match token.split_once('=') {
+            Some((key, value)) => (key, Some(value.trim_matches(['"', '\'']))),
+            None => (token, None),
+        };
+
+        match (key, value) {
+            ("hl_lines" | "hl" | "highlight", Some(v)) => {
+                lines.highlight.extend(line_range(v));
+                in_highlight = true;
+                continue;
+            },
+            (v, None) if in_highlight && line_range(v.trim_matches(['"', '\''])).is_some() => {
+                lines.highlight.extend(line_range(v.trim_matches(['"', '\''])));
+                continue;
+            },
+            ("linenos" | "linenums" | "numbers", None) => lines.numbers = true,
+            ("start" | "linenostart", Some(v)) => lines.start = v.parse().unwrap_or(lines.start),
+            (v, None) if lang.is_none() && (i == 0 || v.starts_with('.')) => lang = Some(v.trim_start_matches('.').to_string()),
+            _ => (),
+        }
+        in_highlight = false;
+    }
+
+    (lang, lines)
+}
+
+fn line_range(s: &str) -> Option<std::ops::RangeInclusive<usize>> {
+    let (start, end) = match s.split_once("..=").or_else(|| s.split_once("..")).or_else(|| s.split_once('-')) {
+        Some((start, end)) => (start.parse().ok()?, end.parse().ok()?),
+        None => s.parse::<usize>().ok().map(|v| (v, v))?,
+    };
+    Some(start.min(end)..=start.max(end))
+}
+
 // INFO Fork of pulldown_cmark::HtmlWriter with new code processing and minification
 
 enum TableState {
@@ -20,10 +532,20 @@ enum TableState {
     Body,
 }
 
+/// Id of the `count`th reference to a footnote, "fnref-name", "fnref-name-2", ...
+fn reference_id(name: &str, count: usize) -> String {
+    match count {
+        1 => format!("fnref-{name}"),
+        _ => format!("fnref-{name}-{count}"),
+    }
+}
+
 struct HtmlWriter<'a, I, W> {
-    text:   &'a str,
-    iter:   I,
-    writer: W,
+    options: Options,
+    iter:    I,
+    writer:  W,
+
+    heading_id: Option<pulldown_cmark::CowStr<'a>>,
 
     in_non_writing_block: bool,
 
@@ -31,10 +553,12 @@ struct HtmlWriter<'a, I, W> {
     table_alignments: Vec<Alignment>,
     table_cell_index: usize,
     numbers:          std::collections::HashMap<pulldown_cmark::CowStr<'a>, usize>,
+    references:       std::collections::HashMap<pulldown_cmark::CowStr<'a>, usize>,
+    notes:            Vec<(pulldown_cmark::CowStr<'a>, Vec<Event<'a>>)>,
+    images:           Option<&'a crate::images::Images>,
 
-    code_lang:  Option<Lang>,
-    code_start: Option<* const u8>,
-    code_len:   usize,
+    code:      Option<(Option<String>, Lines)>,
+    code_text: String,
 }
 
 impl<'a, I, W> HtmlWriter<'a, I, W>
@@ -42,36 +566,38 @@ where
     I: Iterator<Item = Event<'a>>,
     W: StrWrite,
 {
-    fn new(text: &'a str, iter: I, writer: W) -> Self {
+    fn new(options: Options, iter: I, writer: W) -> Self {
         Self {
-            text,
+            options,
             iter,
             writer,
+            heading_id:           None,
             in_non_writing_block: false,
             table_state:          TableState::Head,
             table_alignments:     Vec::new(),
             table_cell_index:     0,
             numbers:              std::collections::HashMap::new(),
-            code_lang:            None,
-            code_start:           None,
-            code_len:             0,
+            references:           std::collections::HashMap::new(),
+            notes:                Vec::new(),
+            images:               None,
+            code:                 None,
+            code_text:            String::new(),
         }
     }
 
     fn run(mut self) -> Result<(), W::Error> {
+        self.events()?;
+        self.footnotes()
+    }
+
+    fn events(&mut self) -> Result<(), W::Error> {
         while let Some(event) = self.iter.next() {
             match event {
                 Event::Start(tag) => self.start_tag(tag)?,
                 Event::End(tag)    => self.end_tag(tag)?,
                 Event::Text(text) => if !self.in_non_writing_block {
-                    match &self.code_lang {
-                        Some(_) if self.text.as_ptr() <= text.as_ptr() && self.text.as_ptr() as usize + self.text.len() >= text.as_ptr() as usize + text.len() => {
-                            if self.code_start.is_none() {
-                                self.code_start = Some(text.as_ptr());
-                            }
-                            self.code_len += text.len();
-                        }
-                        Some(_) => unreachable!(),
+                    match self.code {
+                        Some(_) => self.code_text.push_str(&text),
                         None => escape_html_body_text(&mut self.writer, &text)?,
                     }
                 },
@@ -80,28 +606,22 @@ where
                     escape_html_body_text(&mut self.writer, &text)?;
                     self.writer.write_str("</code>")?;
                 },
-                Event::InlineMath(text) => {
-                    self.writer.write_str(r#"<span class="math math-inline">"#)?;
-                    escape_html(&mut self.writer, &text)?;
-                    self.writer.write_str("</span>")?;
-                },
-                Event::DisplayMath(text) => {
-                    self.writer.write_str(r#"<span class="math math-display">"#)?;
-                    escape_html(&mut self.writer, &text)?;
-                    self.writer.write_str("</span>")?;
-                },
+                Event::InlineMath(text) => self.math(&text, false)?,
+                Event::DisplayMath(text) => self.math(&text, true)?,
                 Event::Html(html) | Event::InlineHtml(html) => self.writer.write_str(&html)?,
                 Event::SoftBreak => self.writer.write_str("\n")?,
                 Event::HardBreak => self.writer.write_str("<br />")?,
                 Event::Rule      => self.writer.write_str("<hr />")?,
                 Event::FootnoteReference(name) => {
                     let len = self.numbers.len() + 1;
-                    self.writer.write_str("<sup class=\"footnote-reference\"><a href=\"#")?;
-                    escape_html(&mut self.writer, &name)?;
-                    self.writer.write_str("\">")?;
-                    let number = *self.numbers.entry(name).or_insert(len);
-                    write!(&mut self.writer, "{}", number)?;
-                    self.writer.write_str("</a></sup>")?;
+                    let number = *self.numbers.entry(name.clone()).or_insert(len);
+                    let count = self.references.entry(name.clone()).or_default();
+                    *count += 1;
+                    self.writer.write_str("<sup class=\"footnote-reference\" id=\"")?;
+                    escape_html(&mut self.writer, &reference_id(&name, *count))?;
+                    self.writer.write_str("\"><a href=\"#fn-")?;
+                    escape_href(&mut self.writer, &name)?;
+                    write!(&mut self.writer, "\">{}</a></sup>", number)?;
                 },
                 Event::TaskListMarker(true)  => self.writer.write_str("<input disabled=\"\" type=\"checkbox\" checked=\"\"/>")?,
                 Event::TaskListMarker(false) => self.writer.write_str("<input disabled=\"\" type=\"checkbox\"/>")?,
@@ -121,6 +641,7 @@ where
                     self.writer.write_str(" id=\"")?;
                     escape_html(&mut self.writer, &id)?;
                     self.writer.write_str("\"")?;
+                    self.heading_id = Some(id);
                 }
                 let mut classes = classes.iter();
                 if let Some(class) = classes.next() {
@@ -185,10 +706,14 @@ where
                 self.writer.write_str(&format!("<blockquote{}>", class_str))
             },
             Tag::CodeBlock(info) => {
-                if let CodeBlockKind::Fenced(info) = info {
-                    self.code_lang = info.split(' ').next().and_then(|s| Lang::form_str(s));
+                self.code = Some(match info {
+                    CodeBlockKind::Fenced(info) => code_info(&info),
+                    CodeBlockKind::Indented => (None, Lines::default()),
+                });
+                match self.options.classes {
+                    Classes::Inline => write!(self.writer, "<pre style=\"{}\"><code>", crate::theme::block_style()),
+                    _ => self.writer.write_str("<pre><code>"),
                 }
-                self.writer.write_str("<pre><code>")
             },
             Tag::List(Some(1)) => self.writer.write_str("<ol>"),
             Tag::List(Some(start)) => {
@@ -225,24 +750,55 @@ where
                 self.writer.write_str("\">")
             },
             Tag::Image { link_type: _, dest_url, title, id: _ } => {
+                let image = self.images.and_then(|v| v.get(&dest_url));
+                let sizes = image.map(|v| format!("(max-width: {0}px) 100vw, {0}px", v.width)).unwrap_or_default();
+                if let Some(image) = image && !image.webp.is_empty() {
+                    self.writer.write_str("<picture><source type=\"image/webp\"")?;
+                    self.srcset(&image.webp, &sizes)?;
+                    self.writer.write_str(">")?;
+                }
+
                 self.writer.write_str("<img src=\"")?;
                 escape_href(&mut self.writer, &dest_url)?;
-                self.writer.write_str("\" alt=\"")?;
+                self.writer.write_str("\"")?;
+                if let Some(image) = image && !image.srcset.is_empty() {
+                    self.srcset(&image.srcset, &sizes)?;
+                }
+                self.writer.write_str(" alt=\"")?;
                 self.raw_text()?;
                 if !title.is_empty() {
                     self.writer.write_str("\" title=\"")?;
                     escape_html(&mut self.writer, &title)?;
                 }
-                self.writer.write_str("\" />")
+                self.writer.write_str("\"")?;
+                if let Some(image) = image {
+                    write!(&mut self.writer, " width=\"{}\" height=\"{}\"", image.width, image.height)?;
+                }
+                if self.images.is_some() {
+                    self.writer.write_str(" loading=\"lazy\" decoding=\"async\"")?;
+                }
+                self.writer.write_str(" />")?;
+
+                if image.is_some_and(|v| !v.webp.is_empty()) {
+                    self.writer.write_str("</picture>")?;
+                }
+                Ok(())
             },
             Tag::FootnoteDefinition(name) => {
-                self.writer.write_str("<div class=\"footnote-definition\" id=\"")?;
-                escape_html(&mut self.writer, &name)?;
-                self.writer.write_str("\"><sup class=\"footnote-definition-label\">")?;
-                let len = self.numbers.len() + 1;
-                let number = *self.numbers.entry(name).or_insert(len);
-                write!(&mut self.writer, "{}", number)?;
-                self.writer.write_str("</sup>")
+                // INFO Written in `footnotes` after the article, nested definitions are collected too
+                let mut nest = 0;
+                let mut events = Vec::new();
+                for event in self.iter.by_ref() {
+                    match event {
+                        Event::Start(Tag::FootnoteDefinition(_)) => nest += 1,
+                        Event::End(TagEnd::FootnoteDefinition) if nest == 0 => break,
+                        Event::End(TagEnd::FootnoteDefinition) => nest -= 1,
+                        _ => (),
+                    }
+                    events.push(event);
+                }
+                self.notes.push((name, events));
+                Ok(())
             },
             Tag::MetadataBlock(_) => {
                 self.in_non_writing_block = true;
@@ -255,7 +811,14 @@ where
         match tag {
             TagEnd::HtmlBlock                    => (),
             TagEnd::Paragraph                    => self.writer.write_str("</p>")?,
-            TagEnd::Heading(level) => write!(&mut self.writer, "</{}>", level)?,
+            TagEnd::Heading(level) => {
+                if let Some(id) = self.heading_id.take() && self.options.anchors {
+                    self.writer.write_str("<a class=\"heading-anchor\" href=\"#")?;
+                    escape_href(&mut self.writer, &id)?;
+                    self.writer.write_str("\">#</a>")?;
+                }
+                write!(&mut self.writer, "</{}>", level)?
+            },
             TagEnd::Table                        => self.writer.write_str("</tbody></table>")?,
             TagEnd::TableHead => {
                 self.writer.write_str("</tr></thead><tbody>")?;
@@ -270,14 +833,9 @@ where
                 self.table_cell_index += 1;
             }
             TagEnd::CodeBlock => {
-                if let Some(lang) = self.code_lang.take() {
-                    if let Some(ptr) = self.code_start.take() {
-                        let s = unsafe { std::str::from_utf8_unchecked(std::slice::from_raw_parts(ptr, self.code_len)) };
-                        LANG_DB.html(s, lang, &mut self.writer)?;
-                        self.code_start = None;
-                    }
-
-                    self.code_len = 0;
+                if let Some((lang, lines)) = self.code.take() {
+                    LANG_DB.html(&self.code_text, lang.as_deref(), &lines, self.options.classes, &mut self.writer)?;
+                    self.code_text.clear();
                 }
                 self.writer.write_str("</code></pre>")?
             },
@@ -295,45 +853,364 @@ where
             TagEnd::Strikethrough            => self.writer.write_str("</del>")?,
             TagEnd::Link                     => self.writer.write_str("</a>")?,
             TagEnd::Image                    => (), // INFO shouldn't happen, handled in start
-            TagEnd::FootnoteDefinition       => self.writer.write_str("</div>")?,
+            TagEnd::FootnoteDefinition       => (), // INFO shouldn't happen, collected in start
             TagEnd::MetadataBlock(_)         => self.in_non_writing_block = false,
         }
         Ok(())
     }
 
+    /// Ordered list of the collected definitions, referenced ones by their number then the rest
+    /// in the source order, each one with links back to all its references
+    fn footnotes(&mut self) -> Result<(), W::Error> {
+        if self.notes.is_empty() {
+            return Ok(());
+        }
+
+        let mut notes = std::mem::take(&mut self.notes);
+        self.writer.write_str("<section class=\"footnotes\"><ol>")?;
+        let mut position = 0;
+        while let Some(i) = (0..notes.len()).min_by_key(|&i| self.numbers.get(&notes[i].0).copied().unwrap_or(usize::MAX)) {
+            let (name, mut events) = notes.remove(i);
+            let len = self.numbers.len() + 1;
+            let number = *self.numbers.entry(name.clone()).or_insert(len);
+            position += 1;
+
+            self.writer.write_str("<li id=\"fn-")?;
+            escape_html(&mut self.writer, &name)?;
+            if number != position {
+                write!(&mut self.writer, "\" value=\"{}", number)?;
+            }
+            self.writer.write_str("\">")?;
+
+            let mut backrefs = String::new();
+            for count in 1..=self.references.get(&name).copied().unwrap_or_default() {
+                backrefs.push_str(" <a class=\"footnote-backref\" href=\"#");
+                escape_href(&mut backrefs, &reference_id(&name, count)).expect("writing to a String");
+                backrefs.push_str(&format!("\" aria-label=\"Back to reference {number}\">↩"));
+                if count > 1 {
+                    backrefs.push_str(&format!("<sup>{count}</sup>"));
+                }
+                backrefs.push_str("</a>");
+            }
+            match events.last() {
+                Some(Event::End(TagEnd::Paragraph)) => events.insert(events.len() - 1, Event::InlineHtml(backrefs.into())),
+                _ => events.push(Event::InlineHtml(backrefs.into())),
+            }
+
+            // INFO Numbers are shared, so references inside of notes continue the numbering
+            let mut writer = HtmlWriter::new(self.options, events.into_iter(), &mut self.writer);
+            writer.images = self.images;
+            writer.numbers = std::mem::take(&mut self.numbers);
+            writer.references = std::mem::take(&mut self.references);
+            writer.events()?;
+            self.numbers = writer.numbers;
+            self.references = writer.references;
+            notes.extend(writer.notes);
+
+            self.writer.write_str("</li>")?;
+        }
+        self.writer.write_str("</ol></section>")
+    }
+
+    /// " srcset=\"a-480w.png 480w, a.png 1000w\" sizes=\"...\""
+    fn srcset(&mut self, srcset: &[(String, u32)], sizes: &str) -> Result<(), W::Error> {
+        self.writer.write_str(" srcset=\"")?;
+        for (i, (url, width)) in srcset.iter().enumerate() {
+            if i > 0 {
+                self.writer.write_str(", ")?;
+            }
+            escape_href(&mut self.writer, url)?;
+            write!(&mut self.writer, " {}w", width)?;
+        }
+        self.writer.write_str("\" sizes=\"")?;
+        escape_html(&mut self.writer, sizes)?;
+        self.writer.write_str("\"")
+    }
+
+    /// MathML rendered at build time, escaped TeX in a span when it can't be rendered, see `math()`
+    fn math(&mut self, text: &str, display: bool) -> Result<(), W::Error> {
+        match crate::mathml::render(text, display) {
+            Ok(mathml) => self.writer.write_str(&mathml),
+            Err(_) => {
+                self.writer.write_str(if display { r#"<span class="math math-display">"# } else { r#"<span class="math math-inline">"# })?;
+                escape_html(&mut self.writer, text)?;
+                self.writer.write_str("</span>")
+            },
+        }
+    }
+
     fn raw_text(&mut self) -> Result<(), W::Error> {
-        let mut nest = 0;
-        while let Some(event) = self.iter.next() {
-            match event {
-                Event::Start(_) => nest += 1,
-                Event::End(_) => {
-                    if nest == 0 {
-                        break;
-                    }
-                    nest -= 1;
-                },
-                Event::Html(_) => (),
-                Event::InlineHtml(text) | Event::Code(text) | Event::Text(text) => escape_html(&mut self.writer, &text)?,
-                Event::InlineMath(text) => {
-                    self.writer.write_str("$")?;
-                    escape_html(&mut self.writer, &text)?;
-                    self.writer.write_str("$")?;
-                },
-                Event::DisplayMath(text) => {
-                    self.writer.write_str("$$")?;
-                    escape_html(&mut self.writer, &text)?;
-                    self.writer.write_str("$$")?;
-                },
-                Event::SoftBreak | Event::HardBreak | Event::Rule => self.writer.write_str(" ")?,
-                Event::FootnoteReference(name) => {
-                    let len = self.numbers.len() + 1;
-                    let number = *self.numbers.entry(name).or_insert(len);
-                    write!(&mut self.writer, "[{}]", number)?;
-                },
-                Event::TaskListMarker(true)  => self.writer.write_str("[x]")?,
-                Event::TaskListMarker(false) => self.writer.write_str("[ ]")?,
+        plain_text(&mut self.iter, &mut self.numbers, |v| escape_html(&mut self.writer, v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heading_ids() {
+        [
+            ("# Hello, World!",           Options::default(),                              "<h1 id=\"hello-world\">Hello, World!</h1>"),
+            ("# Заголовок `код`",         Options::default(),                              "<h1 id=\"заголовок-код\">Заголовок <code>код</code></h1>"),
+            ("## A\n## A\n## A-1",        Options::default(),                              "<h2 id=\"a\">A</h2><h2 id=\"a-1\">A</h2><h2 id=\"a-1-1\">A-1</h2>"),
+            ("# !!!",                     Options::default(),                              "<h1 id=\"section\">!!!</h1>"),
+            ("## Title",                  Options { anchors: true, ..Options::default() }, "<h2 id=\"title\">Title<a class=\"heading-anchor\" href=\"#title\">#</a></h2>"),
+        ].into_iter().enumerate().for_each(|(i, (input, options, expected))| {
+            let result = Markdown::new(input, options, "").to_string();
+            if result != expected {
+                panic!("\n[Test {i} failed]\nInput: {input:?}\nResult:   {result}\nExpected: {expected}\n");
+            }
+        });
+    }
+
+    #[test]
+    fn test_code_info() {
+        let lines = |numbers, start, highlight: &[std::ops::RangeInclusive<usize>]| Lines { numbers, start, highlight: highlight.to_vec() };
+        [
+            ("rust",                                   (Some("rust"),     Lines::default())),
+            ("rust ignore",                            (Some("rust"),     Lines::default())),
+            ("",                                       (None,             Lines::default())),
+            ("unknown,linenos",                        (Some("unknown"),  lines(true, 1, &[]))),
+            ("linenos",                                (None,             lines(true, 1, &[]))),
+            ("rust,linenos,hl_lines=3-5,start=10",     (Some("rust"),     lines(true, 10, &[3..=5]))),
+            ("rust,hl_lines=3-5,7,start=x",            (Some("rust"),     lines(false, 1, &[3..=5, 7..=7]))),
+            ("rust hl_lines=\"1 4-2\"",                (Some("rust"),     lines(false, 1, &[1..=1, 2..=4]))),
+            ("{.rust linenos hl=3..5}",                (Some("rust"),     lines(true, 1, &[3..=5]))),
+            ("{linenos .py hl=3..=5 linenostart=0}",   (Some("py"),       lines(true, 0, &[3..=5]))),
+        ].into_iter().for_each(|(input, expected)| {
+            assert_eq!(code_info(input), (expected.0.map(String::from), expected.1), "Input: {input:?}");
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "lang-rust")]
+    fn test_code_lines() {
+        [
+            ("```\na < b\n```",                       "<pre><code>a &lt; b\n</code></pre>"),
+            ("```text,linenos,start=9\na\n\nb\n```", "<pre><code><span class=\"line\"><span class=\"line-number\" aria-hidden=\"true\">9</span>a</span>\n<span class=\"line\"><span class=\"line-number\" aria-hidden=\"true\">10</span></span>\n<span class=\"line\"><span class=\"line-number\" aria-hidden=\"true\">11</span>b</span>\n</code></pre>"),
+            ("```text,hl_lines=2\na\nb\n```",        "<pre><code><span class=\"line\">a</span>\n<span class=\"line highlight\">b</span>\n</code></pre>"),
+            ("```rust,hl_lines=2\n/* a\nb */\n```",  "<pre><code><span class=\"line\"><span class=\"comm\">/* a</span></span>\n<span class=\"line highlight\"><span class=\"comm\">b */</span></span>\n</code></pre>"),
+            ("- ```rust\n  let\n  ```",             "<ul><li><pre><code><span class=\"kw\">let</span>\n</code></pre></li></ul>"),
+        ].into_iter().enumerate().for_each(|(i, (input, expected))| {
+            let result = Markdown::new(input, Options::default(), "").to_string();
+            if result != expected {
+                panic!("\n[Test {i} failed]\nInput: {input:?}\nResult:   {result}\nExpected: {expected}\n");
+            }
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "lang-rust")]
+    fn test_inline_styles() {
+        let options = Options { classes: Classes::Inline, ..Options::default() };
+        let result = Markdown::new("```rust,hl_lines=1\nlet\n```", options, "").to_string();
+        let expected = format!(
+            "<pre style=\"{}\"><code><span style=\"{}\"><span style=\"{}\">let</span></span>\n</code></pre>",
+            crate::theme::block_style(), crate::theme::line_style(true), crate::theme::style("keyword"),
+        );
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_toc() {
+        [
+            ("Text only",                       ""),
+            ("## A\n### B\n### C\n## D",        "<nav class=\"toc\"><ul><li><a href=\"#a\">A</a><ul><li><a href=\"#b\">B</a></li><li><a href=\"#c\">C</a></li></ul></li><li><a href=\"#d\">D</a></li></ul></nav>"),
+            ("## A\n#### B\n## C",              "<nav class=\"toc\"><ul><li><a href=\"#a\">A</a><ul><li><a href=\"#b\">B</a></li></ul></li><li><a href=\"#c\">C</a></li></ul></nav>"),
+            ("### A\n## B",                     "<nav class=\"toc\"><ul><li><a href=\"#a\">A</a></li><li><a href=\"#b\">B</a></li></ul></nav>"),
+            ("## A & *B*",                      "<nav class=\"toc\"><ul><li><a href=\"#a-b\">A &amp; B</a></li></ul></nav>"),
+        ].into_iter().enumerate().for_each(|(i, (input, expected))| {
+            let result = Document::new(input, Extensions::default(), "").toc().to_string();
+            if result != expected {
+                panic!("\n[Test {i} failed]\nInput: {input:?}\nResult:   {result}\nExpected: {expected}\n");
+            }
+        });
+    }
+
+    #[test]
+    fn test_ids() {
+        let extensions = Extensions::new(&[Extension::HeadingAttributes]);
+        let result = Document::new("# A {#b}\n# B\n# C {#b}\n# B", extensions, "").toc().to_string();
+        assert_eq!(result.matches("href=\"#").count(), 4);
+        assert!(result.contains("<a href=\"#b\">A</a></li><li><a href=\"#b-1\">B</a></li><li><a href=\"#b\">C</a></li><li><a href=\"#b-2\">B</a>"), "{result}");
+        assert_eq!(Document::new("# A {#b}\n# B\n# C {#b}\n# B", extensions, "").ids(), [("duplicate heading id \"b\"".to_string(), 13)]);
+        assert_eq!(Document::new("# A {#a}\n# A", extensions, "").ids(), []);
+    }
+
+    #[test]
+    fn test_rebase() {
+        [
+            ("![a](cat.png)",                   "<p><img src=\"my-post/cat.png\" alt=\"a\" /></p>"),
+            ("![a](./img/cat.png \"T\")",       "<figure><img src=\"my-post/img/cat.png\" alt=\"a\" /><figcaption>T</figcaption></figure>"),
+            ("![a](/cat.png)",                  "<p><img src=\"/cat.png\" alt=\"a\" /></p>"),
+            ("![a](https://x.org/cat.png)",     "<p><img src=\"https://x.org/cat.png\" alt=\"a\" /></p>"),
+            ("[link](cat.png)",                 "<p><a href=\"cat.png\">link</a></p>"),
+        ].into_iter().enumerate().for_each(|(i, (input, expected))| {
+            let result = Markdown::new(input, Options::default(), "my-post/").to_string();
+            if result != expected {
+                panic!("\n[Test {i} failed]\nInput: {input:?}\nResult:   {result}\nExpected: {expected}\n");
             }
+        });
+    }
+
+    #[test]
+    fn test_links() {
+        let (links, ids) = Document::new("# A\n\n[x](./b.html#c) ![i](i.png)\n\n## B\n\n[y](#a)", Extensions::default(), "p/").links();
+        assert_eq!(links, [("./b.html#c".to_string(), 5), ("p/i.png".to_string(), 21), ("#a".to_string(), 40)]);
+        assert_eq!(ids, ["a", "b"]);
+    }
+
+    #[test]
+    fn test_images() {
+        use crate::images::{ Image, Images };
+        let known = [
+            ("p/a.jpg", Image { width: 800, height: 600, srcset: Vec::new(), webp: Vec::new() }),
+            ("p/b.png", Image {
+                width:  1000,
+                height: 500,
+                srcset: vec![("p/b-480w.png".to_string(), 480), ("p/b.png".to_string(), 1000)],
+                webp:   vec![("p/b-480w.webp".to_string(), 480), ("p/b.webp".to_string(), 1000)],
+            }),
+        ].into_iter().map(|(k, v)| (k.to_string(), v)).collect::<Images>();
+
+        [
+            ("![A](a.jpg)",                   "<p><img src=\"p/a.jpg\" alt=\"A\" width=\"800\" height=\"600\" loading=\"lazy\" decoding=\"async\" /></p>"),
+            ("![R](https://x.org/r.png)",     "<p><img src=\"https://x.org/r.png\" alt=\"R\" loading=\"lazy\" decoding=\"async\" /></p>"),
+            ("![A](a.jpg \"A <caption>\")",   "<figure><img src=\"p/a.jpg\" alt=\"A\" width=\"800\" height=\"600\" loading=\"lazy\" decoding=\"async\" /><figcaption>A &lt;caption&gt;</figcaption></figure>"),
+            ("Text ![A](a.jpg \"T\")",        "<p>Text <img src=\"p/a.jpg\" alt=\"A\" title=\"T\" width=\"800\" height=\"600\" loading=\"lazy\" decoding=\"async\" /></p>"),
+            ("![B](b.png)",
+             "<p><picture><source type=\"image/webp\" srcset=\"p/b-480w.webp 480w, p/b.webp 1000w\" sizes=\"(max-width: 1000px) 100vw, 1000px\">\
+              <img src=\"p/b.png\" srcset=\"p/b-480w.png 480w, p/b.png 1000w\" sizes=\"(max-width: 1000px) 100vw, 1000px\" alt=\"B\" width=\"1000\" height=\"500\" loading=\"lazy\" decoding=\"async\" />\
+              </picture></p>"),
+        ].into_iter().for_each(|(input, expected)| {
+            assert_eq!(Markdown { images: Some(&known), ..Markdown::new(input, Options::default(), "p/") }.to_string(), expected, "Input: {input:?}");
+        });
+
+        // Without images, e.g. in feeds, only captions are added
+        assert_eq!(Markdown::new("![A](a.jpg \"Cap\")", Options::default(), "").to_string(), "<figure><img src=\"a.jpg\" alt=\"A\" /><figcaption>Cap</figcaption></figure>");
+        assert_eq!(Document::new("![A](a.jpg) [l](l.html) ![B](https://x.org/b.png)", Extensions::default(), "p/").images(), ["p/a.jpg", "https://x.org/b.png"]);
+    }
+
+    #[test]
+    fn test_text() {
+        let extensions = Extensions::new(&[Extension::Footnotes, Extension::MetadataBlocks, Extension::Math, Extension::Tasklists]);
+        let result = text("---\nkey: v\n---\n# Head\n\nA *b*c `d` $x$[^1] <br>\n- [x] e\n\n![alt *i*](i.png)\n\n[^1]: n", extensions);
+        assert_eq!(result.split_whitespace().collect::<Vec<_>>(), ["Head", "A", "bc", "d", "$x$[1]", "[x]e", "alt", "i", "n"]);
+    }
+
+    #[test]
+    fn test_math() {
+        let options = Options { extensions: Extensions::new(&[Extension::Math]), ..Options::default() };
+        let result = Markdown::new("Area $\\pi r^2$ and $$\\unknown$$", options, "").to_string();
+        assert!(result.starts_with("<p>Area <math xmlns=\"http://www.w3.org/1998/Math/MathML\" class=\"math math-inline\"><semantics><mrow><mi>π</mi>"), "{result}");
+        assert!(result.ends_with(" and <span class=\"math math-display\">\\unknown</span></p>"), "{result}");
+        let result = Markdown::new(&format!("${}x$", "\\sqrt".repeat(100_000)), options, "").to_string();
+        assert!(result.starts_with("<p><span class=\"math math-inline\">\\sqrt\\sqrt"), "{}", &result[..100]);
+
+        assert_eq!(Document::new("Area $\\pi r^2$\n\n$$\\unknown$$ and `$\\unknown$`", options.extensions, "").math().into_iter().map(|v| v.1).collect::<Vec<_>>(), [16]);
+        assert_eq!(Document::new("$$\\unknown$$", Extensions::default(), "").math(), []);
+        assert_eq!(Markdown::new("From $1 to $2", Options::default(), "").to_string(), "<p>From $1 to $2</p>");
+    }
+
+    #[test]
+    fn test_footnotes() {
+        let options = Options { extensions: Extensions::new(&[Extension::Footnotes]), ..Options::default() };
+        let backref = |id: &str, number: usize, sup: &str| format!("<a class=\"footnote-backref\" href=\"#fnref-{id}\" aria-label=\"Back to reference {number}\">↩{sup}</a>");
+
+        // Definitions are ordered by the first reference, unreferenced ones go last
+        let result = Markdown::new("A[^b] B[^a] C[^b]\n\n[^a]: One[^c]\n\n[^u]: Unused\n\n[^b]: Two\n\n[^c]:\n    ```\n    code\n    ```", options, "").to_string();
+        assert_eq!(result, [
+            "<p>A<sup class=\"footnote-reference\" id=\"fnref-b\"><a href=\"#fn-b\">1</a></sup> ",
+            "B<sup class=\"footnote-reference\" id=\"fnref-a\"><a href=\"#fn-a\">2</a></sup> ",
+            "C<sup class=\"footnote-reference\" id=\"fnref-b-2\"><a href=\"#fn-b\">1</a></sup></p>",
+            "<section class=\"footnotes\"><ol>",
+            &format!("<li id=\"fn-b\"><p>Two {} {}</p></li>", backref("b", 1, ""), backref("b-2", 1, "<sup>2</sup>")),
+            &format!("<li id=\"fn-a\"><p>One<sup class=\"footnote-reference\" id=\"fnref-c\"><a href=\"#fn-c\">3</a></sup> {}</p></li>", backref("a", 2, "")),
+            &format!("<li id=\"fn-c\"><pre><code>code\n</code></pre> {}</li>", backref("c", 3, "")),
+            "<li id=\"fn-u\"><p>Unused</p></li>",
+            "</ol></section>",
+        ].concat());
+
+        // Numbers skipped by undefined references are kept
+        let result = Markdown::new("A[^x] B[^y]\n\n[^y]: Y", Options { extensions: Extensions::new(&[Extension::OldFootnotes]), ..options }, "").to_string();
+        assert!(result.contains(&format!("<li id=\"fn-y\" value=\"2\"><p>Y {}</p></li>", backref("y", 2, ""))), "{result}");
+
+        assert_eq!(Markdown::new("No notes", options, "").to_string(), "<p>No notes</p>");
+    }
+
+    #[test]
+    fn test_footnote_warnings() {
+        let input = "A[^a] B[^x]\n\n[^a]: One\n\n[^u]: Unused";
+        for extension in [Extension::Footnotes, Extension::OldFootnotes] {
+            assert_eq!(Document::new(input, Extensions::new(&[extension]), "").footnotes(), [
+                ("undefined footnote \"x\"".to_string(), 7),
+                ("unreferenced footnote \"u\"".to_string(), 24),
+            ], "{extension:?}");
         }
-        Ok(())
+        assert_eq!(Document::new(input, Extensions::new(&[]), "").footnotes(), []);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_tags() {
+        let all = Options { extensions: Extensions::new(<Extension as clap::ValueEnum>::value_variants()), ..Options::default() };
+        [
+            ("# A {#id .c1 .c2 key=v flag}",                    "<h1 id=\"id\" class=\"c1 c2\" key=\"v\" flag=\"\">A</h1>"),
+            ("| a | b | c | d |\n|:--|:-:|--:|---|\n| 1 | 2 | 3 | 4 |",
+             "<table><thead><tr><th style=\"text-align: left\">a</th><th style=\"text-align: center\">b</th><th style=\"text-align: right\">c</th><th>d</th></tr></thead>\
+              <tbody><tr><td style=\"text-align: left\">1</td><td style=\"text-align: center\">2</td><td style=\"text-align: right\">3</td><td>4</td></tr></tbody></table>"),
+            ("> quote",                                         "<blockquote><p>quote</p></blockquote>"),
+            ("> [!NOTE]\n> n",                                  "<blockquote class=\"markdown-alert-note\"><p>n</p></blockquote>"),
+            ("> [!TIP]\n> t",                                   "<blockquote class=\"markdown-alert-tip\"><p>t</p></blockquote>"),
+            ("> [!IMPORTANT]\n> i",                             "<blockquote class=\"markdown-alert-important\"><p>i</p></blockquote>"),
+            ("> [!WARNING]\n> w",                               "<blockquote class=\"markdown-alert-warning\"><p>w</p></blockquote>"),
+            ("> [!CAUTION]\n> c",                               "<blockquote class=\"markdown-alert-caution\"><p>c</p></blockquote>"),
+            ("    indented",                                    "<pre><code>indented</code></pre>"),
+            ("1. a\n2. b",                                      "<ol><li>a</li><li>b</li></ol>"),
+            ("3. a",                                            "<ol start=\"3\"><li>a</li></ol>"),
+            ("- a\n- [x] b\n- [ ] c",                           "<ul><li>a</li><li><input disabled=\"\" type=\"checkbox\" checked=\"\"/>b</li><li><input disabled=\"\" type=\"checkbox\"/>c</li></ul>"),
+            ("Term\n: Definition",                              "<dl><dt>Term</dt><dd>Definition</dd></dl>"),
+            ("a ~x~ b ^y^ *e* **s** ~~d~~",                     "<p>a <sub>x</sub> b <sup>y</sup> <em>e</em> <strong>s</strong> <del>d</del></p>"),
+            ("<a@b.c> [l](u \"T\") ![i *x*](p.png \"T\")",      "<p><a href=\"mailto:a@b.c\">a@b.c</a> <a href=\"u\" title=\"T\">l</a> <img src=\"p.png\" alt=\"i x\" title=\"T\" /></p>"),
+            ("Note[^1]\n\n[^1]: Text",                          "<p>Note<sup class=\"footnote-reference\" id=\"fnref-1\"><a href=\"#fn-1\">1</a></sup></p>\
+                                                                 <section class=\"footnotes\"><ol><li id=\"fn-1\"><p>Text \
+                                                                 <a class=\"footnote-backref\" href=\"#fnref-1\" aria-label=\"Back to reference 1\">↩</a></p></li></ol></section>"),
+            ("---\nk: v\n---\n\nAfter",                         "<p>After</p>"),
+            ("+++\nk = 1\n+++\n\nAfter",                        "<p>After</p>"),
+            ("<div>\nhtml\n</div>",                             "<div>\nhtml\n</div>"),
+            ("[[Page]] [[Page|alias]]",                         "<p><a href=\"Page\">Page</a> <a href=\"Page\">alias</a></p>"),
+            ("\"quoted\" -- dash...",                           "<p>“quoted” – dash…</p>"),
+            ("line  \nbreak\n\n***",                            "<p>line<br />break</p><hr />"),
+        ].into_iter().enumerate().for_each(|(i, (input, expected))| {
+            let result = Markdown::new(input, all, "").to_string();
+            if result != expected {
+                panic!("\n[Test {i} failed]\nInput: {input:?}\nResult:   {result}\nExpected: {expected}\n");
+            }
+        });
+    }
+
+    #[test]
+    fn test_extensions() {
+        [
+            ("- [x] b",                 "<ul><li>[x] b</li></ul>"),
+            ("Term\n: Definition",      "<p>Term\n: Definition</p>"),
+            ("> [!NOTE]\n> n",          "<blockquote><p>[!NOTE]\nn</p></blockquote>"),
+            ("\"q\" a ~x~ b",           "<p>\"q\" a <del>x</del> b</p>"),
+            ("# A {#id}",               "<h1 id=\"a-id\">A {#id}</h1>"),
+        ].into_iter().enumerate().for_each(|(i, (input, expected))| {
+            let result = Markdown::new(input, Options::default(), "").to_string();
+            if result != expected {
+                panic!("\n[Test {i} failed]\nInput: {input:?}\nResult:   {result}\nExpected: {expected}\n");
+            }
+        });
+
+        let extensions = Extensions::default().with(&[(Extension::Footnotes, true), (Extension::Math, true), (Extension::Tables, false)]);
+        assert_eq!(format!("{extensions:?}"), "[Footnotes, Strikethrough, MetadataBlocks, Math]");
+        assert_eq!(Extensions::new(&[Extension::OldFootnotes]).with(&[(Extension::OldFootnotes, false)]), Extensions::new(&[Extension::Footnotes]));
+
+        let options: Options = serde_json::from_str(r#"{"extensions": ["footnotes", "smart-punctuation"]}"#).unwrap();
+        assert_eq!(options.extensions, Extensions::new(&[Extension::Footnotes, Extension::SmartPunctuation]));
+        assert_eq!(serde_json::to_string(&options.extensions).unwrap(), r#"["footnotes","smart-punctuation"]"#);
+        assert_eq!(Markdown::new("\"q\" $x$", options, "").to_string(), "<p>“q” $x$</p>");
+    }
+}
@@ -0,0 +1,651 @@
+// INFO TeX to MathML Core at build time, covers the common subset of LaTeX math: symbols, scripts,
+// fractions, roots, accents, fonts, delimiters and matrix-like environments.
+// Unknown commands are errors, so the caller can keep the source TeX instead of rendering it wrong
+
+use pulldown_cmark_escape::{ escape_html, escape_html_body_text };
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    /// Italic identifier
+    Ident,
+    /// Upright identifier, e.g. capital Greek letters
+    Upright,
+    /// Operator or relation
+    Op,
+    /// Large operator, limits are under and over it in display mode
+    Large,
+    /// Integral, limits always stay at the side
+    Integral,
+    /// Function name like "sin"
+    Func,
+    /// Function name with limits under it in display mode, like "lim"
+    Limit,
+}
+
+const SYMBOLS: &[(&str, &str, Kind)] = &[
+    // Greek
+    ("alpha", "α", Kind::Ident), ("beta", "β", Kind::Ident), ("gamma", "γ", Kind::Ident), ("delta", "δ", Kind::Ident),
+    ("epsilon", "ϵ", Kind::Ident), ("varepsilon", "ε", Kind::Ident), ("zeta", "ζ", Kind::Ident), ("eta", "η", Kind::Ident),
+    ("theta", "θ", Kind::Ident), ("vartheta", "ϑ", Kind::Ident), ("iota", "ι", Kind::Ident), ("kappa", "κ", Kind::Ident),
+    ("lambda", "λ", Kind::Ident), ("mu", "μ", Kind::Ident), ("nu", "ν", Kind::Ident), ("xi", "ξ", Kind::Ident),
+    ("pi", "π", Kind::Ident), ("varpi", "ϖ", Kind::Ident), ("rho", "ρ", Kind::Ident), ("varrho", "ϱ", Kind::Ident),
+    ("sigma", "σ", Kind::Ident), ("varsigma", "ς", Kind::Ident), ("tau", "τ", Kind::Ident), ("upsilon", "υ", Kind::Ident),
+    ("phi", "ϕ", Kind::Ident), ("varphi", "φ", Kind::Ident), ("chi", "χ", Kind::Ident), ("psi", "ψ", Kind::Ident),
+    ("omega", "ω", Kind::Ident),
+    ("Gamma", "Γ", Kind::Upright), ("Delta", "Δ", Kind::Upright), ("Theta", "Θ", Kind::Upright), ("Lambda", "Λ", Kind::Upright),
+    ("Xi", "Ξ", Kind::Upright), ("Pi", "Π", Kind::Upright), ("Sigma", "Σ", Kind::Upright), ("Upsilon", "Υ", Kind::Upright),
+    ("Phi", "Φ", Kind::Upright), ("Psi", "Ψ", Kind::Upright), ("Omega", "Ω", Kind::Upright),
+    // Letter-like
+    ("infty", "∞", Kind::Upright), ("partial", "∂", Kind::Upright), ("nabla", "∇", Kind::Upright), ("emptyset", "∅", Kind::Upright),
+    ("varnothing", "∅", Kind::Upright), ("hbar", "ℏ", Kind::Ident), ("ell", "ℓ", Kind::Ident), ("Re", "ℜ", Kind::Upright),
+    ("Im", "ℑ", Kind::Upright), ("aleph", "ℵ", Kind::Upright), ("angle", "∠", Kind::Upright), ("triangle", "△", Kind::Upright),
+    ("prime", "′", Kind::Op),
+    // Binary operators
+    ("pm", "±", Kind::Op), ("mp", "∓", Kind::Op), ("times", "×", Kind::Op), ("div", "÷", Kind::Op),
+    ("cdot", "⋅", Kind::Op), ("ast", "∗", Kind::Op), ("star", "⋆", Kind::Op), ("circ", "∘", Kind::Op),
+    ("bullet", "∙", Kind::Op), ("oplus", "⊕", Kind::Op), ("ominus", "⊖", Kind::Op), ("otimes", "⊗", Kind::Op),
+    ("oslash", "⊘", Kind::Op), ("odot", "⊙", Kind::Op), ("cup", "∪", Kind::Op), ("cap", "∩", Kind::Op),
+    ("setminus", "∖", Kind::Op), ("wedge", "∧", Kind::Op), ("land", "∧", Kind::Op), ("vee", "∨", Kind::Op),
+    ("lor", "∨", Kind::Op), ("neg", "¬", Kind::Op), ("lnot", "¬", Kind::Op), ("forall", "∀", Kind::Op),
+    ("exists", "∃", Kind::Op), ("nexists", "∄", Kind::Op), ("mod", "mod", Kind::Op), ("bmod", "mod", Kind::Op),
+    // Relations and arrows
+    ("leq", "≤", Kind::Op), ("le", "≤", Kind::Op), ("geq", "≥", Kind::Op), ("ge", "≥", Kind::Op),
+    ("neq", "≠", Kind::Op), ("ne", "≠", Kind::Op), ("approx", "≈", Kind::Op), ("equiv", "≡", Kind::Op),
+    ("sim", "∼", Kind::Op), ("simeq", "≃", Kind::Op), ("cong", "≅", Kind::Op), ("propto", "∝", Kind::Op),
+    ("ll", "≪", Kind::Op), ("gg", "≫", Kind::Op), ("in", "∈", Kind::Op), ("notin", "∉", Kind::Op),
+    ("ni", "∋", Kind::Op), ("subset", "⊂", Kind::Op), ("supset", "⊃", Kind::Op), ("subseteq", "⊆", Kind::Op),
+    ("supseteq", "⊇", Kind::Op), ("mid", "∣", Kind::Op), ("parallel", "∥", Kind::Op), ("perp", "⊥", Kind::Op),
+    ("models", "⊨", Kind::Op), ("vdash", "⊢", Kind::Op), ("to", "→", Kind::Op), ("rightarrow", "→", Kind::Op),
+    ("leftarrow", "←", Kind::Op), ("gets", "←", Kind::Op), ("leftrightarrow", "↔", Kind::Op), ("Rightarrow", "⇒", Kind::Op),
+    ("Leftarrow", "⇐", Kind::Op), ("Leftrightarrow", "⇔", Kind::Op), ("iff", "⟺", Kind::Op), ("implies", "⟹", Kind::Op),
+    ("mapsto", "↦", Kind::Op), ("longrightarrow", "⟶", Kind::Op), ("longleftarrow", "⟵", Kind::Op), ("uparrow", "↑", Kind::Op),
+    ("downarrow", "↓", Kind::Op),
+    // Dots and delimiters
+    ("ldots", "…", Kind::Op), ("dots", "…", Kind::Op), ("cdots", "⋯", Kind::Op), ("vdots", "⋮", Kind::Op),
+    ("ddots", "⋱", Kind::Op), ("langle", "⟨", Kind::Op), ("rangle", "⟩", Kind::Op), ("lceil", "⌈", Kind::Op),
+    ("rceil", "⌉", Kind::Op), ("lfloor", "⌊", Kind::Op), ("rfloor", "⌋", Kind::Op), ("lvert", "|", Kind::Op),
+    ("rvert", "|", Kind::Op), ("vert", "|", Kind::Op), ("lVert", "‖", Kind::Op), ("rVert", "‖", Kind::Op),
+    ("Vert", "‖", Kind::Op), ("backslash", "\\", Kind::Op), ("colon", ":", Kind::Op),
+    // Large operators
+    ("sum", "∑", Kind::Large), ("prod", "∏", Kind::Large), ("coprod", "∐", Kind::Large), ("bigcup", "⋃", Kind::Large),
+    ("bigcap", "⋂", Kind::Large), ("bigoplus", "⨁", Kind::Large), ("bigotimes", "⨂", Kind::Large), ("bigvee", "⋁", Kind::Large),
+    ("bigwedge", "⋀", Kind::Large),
+    ("int", "∫", Kind::Integral), ("iint", "∬", Kind::Integral), ("iiint", "∭", Kind::Integral), ("oint", "∮", Kind::Integral),
+    // Functions
+    ("sin", "sin", Kind::Func), ("cos", "cos", Kind::Func), ("tan", "tan", Kind::Func), ("cot", "cot", Kind::Func),
+    ("sec", "sec", Kind::Func), ("csc", "csc", Kind::Func), ("arcsin", "arcsin", Kind::Func), ("arccos", "arccos", Kind::Func),
+    ("arctan", "arctan", Kind::Func), ("sinh", "sinh", Kind::Func), ("cosh", "cosh", Kind::Func), ("tanh", "tanh", Kind::Func),
+    ("log", "log", Kind::Func), ("ln", "ln", Kind::Func), ("lg", "lg", Kind::Func), ("exp", "exp", Kind::Func),
+    ("arg", "arg", Kind::Func), ("deg", "deg", Kind::Func), ("dim", "dim", Kind::Func), ("hom", "hom", Kind::Func),
+    ("ker", "ker", Kind::Func),
+    ("lim", "lim", Kind::Limit), ("liminf", "lim inf", Kind::Limit), ("limsup", "lim sup", Kind::Limit), ("max", "max", Kind::Limit),
+    ("min", "min", Kind::Limit), ("sup", "sup", Kind::Limit), ("inf", "inf", Kind::Limit), ("det", "det", Kind::Limit),
+    ("gcd", "gcd", Kind::Limit), ("Pr", "Pr", Kind::Limit),
+];
+
+/// Accents over or under the argument
+const ACCENTS: &[(&str, &str, bool)] = &[
+    ("hat", "^", true), ("widehat", "^", true), ("bar", "‾", true), ("overline", "‾", true),
+    ("vec", "→", true), ("dot", "˙", true), ("ddot", "¨", true), ("tilde", "~", true),
+    ("widetilde", "~", true), ("overrightarrow", "→", true), ("overbrace", "⏞", true),
+    ("underline", "_", false), ("underbrace", "⏟", false),
+];
+
+/// Spacing commands with their widths
+const SPACES: &[(&str, &str)] = &[
+    (",", "0.1667em"), (":", "0.2222em"), (">", "0.2222em"), (";", "0.2778em"),
+    (" ", "0.25em"), ("quad", "1em"), ("qquad", "2em"),
+];
+
+/// Environments with their (open, close) delimiters
+const ENVIRONMENTS: &[(&str, &str, &str)] = &[
+    ("matrix", "", ""), ("pmatrix", "(", ")"), ("bmatrix", "[", "]"), ("Bmatrix", "{", "}"),
+    ("vmatrix", "|", "|"), ("Vmatrix", "‖", "‖"), ("cases", "{", ""), ("aligned", "", ""),
+    ("align", "", ""), ("align*", "", ""), ("gathered", "", ""), ("array", "", ""),
+];
+
+/// Nesting of groups and command arguments, the parser is recursive and deeper TeX would overflow the stack
+const MAX_DEPTH: usize = 100;
+
+/// `<math>` element for `tex`, a block when `display`
+pub fn render(tex: &str, display: bool) -> Result<String, String> {
+    let mut parser = Parser { s: tex, pos: 0, display, depth: 0 };
+    let nodes = parser.row(Stop::End)?;
+
+    let mut s = String::with_capacity(tex.len() * 8);
+    s.push_str(match display {
+        true => r#"<math xmlns="http://www.w3.org/1998/Math/MathML" display="block" class="math math-display">"#,
+        false => r#"<math xmlns="http://www.w3.org/1998/Math/MathML" class="math math-inline">"#,
+    });
+    s.push_str("<semantics><mrow>");
+    nodes.iter().for_each(|v| s.push_str(&v.xml));
+    s.push_str(r#"</mrow><annotation encoding="application/x-tex">"#);
+    escape_html(&mut s, tex).map_err(|_| String::from("can't write"))?;
+    s.push_str("</annotation></semantics></math>");
+    Ok(s)
+}
+
+struct Node {
+    xml:    String,
+    limits: bool,
+}
+
+impl Node {
+    fn new(xml: String) -> Self {
+        Self { xml, limits: false }
+    }
+
+    fn row(nodes: Vec<Node>) -> Self {
+        match <[Node; 1]>::try_from(nodes) {
+            Ok([node]) => node,
+            Err(nodes) => Self::new(format!("<mrow>{}</mrow>", nodes.into_iter().map(|v| v.xml).collect::<String>())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    Command(&'a str),
+    Char(char),
+    End,
+}
+
+/// Where a row of nodes ends
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stop {
+    End,
+    Group,
+    Right,
+    Cell,
+}
+
+struct Parser<'a> {
+    s:       &'a str,
+    pos:     usize,
+    display: bool,
+    /// Atoms being parsed, every recursion goes through `atom()`
+    depth:   usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Token<'a> {
+        self.token_at(self.skip_spaces()).0
+    }
+
+    fn next(&mut self) -> Token<'a> {
+        let (token, end) = self.token_at(self.skip_spaces());
+        self.pos = end;
+        token
+    }
+
+    fn skip_spaces(&self) -> usize {
+        self.s[self.pos..].find(|c: char| !c.is_whitespace()).map_or(self.s.len(), |i| self.pos + i)
+    }
+
+    fn token_at(&self, pos: usize) -> (Token<'a>, usize) {
+        let rest = &self.s[pos..];
+        let Some(c) = rest.chars().next() else {
+            return (Token::End, pos);
+        };
+        if c != '\\' {
+            return (Token::Char(c), pos + c.len_utf8());
+        }
+
+        let name = &rest[1..];
+        let len = match name.find(|c: char| !c.is_ascii_alphabetic()) {
+            Some(0) => name.chars().next().map_or(0, char::len_utf8),
+            Some(n) => n,
+            None => name.len(),
+        };
+        (Token::Command(&name[..len]), pos + 1 + len)
+    }
+
+    /// Nodes up to `stop`, the stop token itself isn't consumed except for "}"
+    fn row(&mut self, stop: Stop) -> Result<Vec<Node>, String> {
+        let mut nodes = Vec::new();
+        loop {
+            match (self.peek(), stop) {
+                (Token::End, Stop::End) => return Ok(nodes),
+                (Token::End, _) => return Err(String::from("missing \"}\" or \"\\right\"")),
+                (Token::Char('}'), Stop::Group) => {
+                    self.next();
+                    return Ok(nodes);
+                },
+                (Token::Char('}'), _) => return Err(String::from("unexpected \"}\"")),
+                (Token::Command("right"), Stop::Right) => return Ok(nodes),
+                (Token::Command("right"), _) => return Err(String::from("\"\\right\" without \"\\left\"")),
+                (Token::Char('&') | Token::Command("\\" | "end"), Stop::Cell) => return Ok(nodes),
+                (Token::Command("end"), _) => return Err(String::from("\"\\end\" without \"\\begin\"")),
+                (Token::Char('&') | Token::Command("\\"), _) => {
+                    self.next();
+                },
+                _ => {
+                    if let Some(node) = self.scripted()? {
+                        nodes.push(node);
+                    }
+                },
+            }
+        }
+    }
+
+    /// Atom with its "^", "_" and "'" scripts
+    fn scripted(&mut self) -> Result<Option<Node>, String> {
+        let mut base = match self.peek() {
+            Token::Char('^' | '_' | '\'') => Node::new(String::from("<mrow></mrow>")),
+            _ => match self.atom()? {
+                Some(node) => node,
+                None => return Ok(None),
+            },
+        };
+
+        let (mut sub, mut sup, mut caret) = (None, Vec::new(), false);
+        loop {
+            match self.peek() {
+                Token::Char('^') if !caret => {
+                    self.next();
+                    sup.push(self.argument()?);
+                    caret = true;
+                },
+                Token::Char('_') if sub.is_none() => {
+                    self.next();
+                    sub = Some(self.argument()?);
+                },
+                Token::Char('\'') if !caret => {
+                    self.next();
+                    sup.push(operator("′"));
+                },
+                Token::Char('^' | '_' | '\'') => return Err(String::from("double script, use braces")),
+                Token::Command(name @ ("limits" | "nolimits")) => {
+                    self.next();
+                    base.limits = name == "limits";
+                },
+                _ => break,
+            }
+        }
+
+        let sup = (!sup.is_empty()).then(|| Node::row(sup).xml);
+        let sub = sub.map(|v| v.xml);
+        let (under, over, both) = match base.limits && self.display {
+            true => ("munder", "mover", "munderover"),
+            false => ("msub", "msup", "msubsup"),
+        };
+        let xml = match (sub, sup) {
+            (None, None) => return Ok(Some(base)),
+            (Some(sub), None) => format!("<{under}>{}{sub}</{under}>", base.xml),
+            (None, Some(sup)) => format!("<{over}>{}{sup}</{over}>", base.xml),
+            (Some(sub), Some(sup)) => format!("<{both}>{}{sub}{sup}</{both}>", base.xml),
+        };
+        Ok(Some(Node::new(xml)))
+    }
+
+    /// Single token or a braced group
+    fn argument(&mut self) -> Result<Node, String> {
+        match self.peek() {
+            Token::Char(c) if c.is_ascii_digit() => {
+                self.next();
+                Ok(Node::new(format!("<mn>{c}</mn>")))
+            },
+            Token::End | Token::Char('}' | '^' | '_' | '&') => Err(String::from("missing argument")),
+            _ => self.atom()?.ok_or_else(|| String::from("missing argument")),
+        }
+    }
+
+    /// Braced group as a single node
+    fn group(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Token::Char('{') => Ok(Node::row(self.row(Stop::Group)?)),
+            _ => Err(String::from("expected \"{\"")),
+        }
+    }
+
+    /// Raw text of a braced group
+    fn braced_text(&mut self) -> Result<&'a str, String> {
+        if self.next() != Token::Char('{') {
+            return Err(String::from("expected \"{\""));
+        }
+        let start = self.pos;
+        let mut depth = 0;
+        for (i, c) in self.s[start..].char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => {
+                    self.pos = start + i + 1;
+                    return Ok(&self.s[start..start + i]);
+                },
+                '}' => depth -= 1,
+                _ => (),
+            }
+        }
+        Err(String::from("missing \"}\""))
+    }
+
+    fn atom(&mut self) -> Result<Option<Node>, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(format!("nested deeper than {MAX_DEPTH} levels"));
+        }
+        self.depth += 1;
+        let node = self.unlimited_atom();
+        self.depth -= 1;
+        node
+    }
+
+    fn unlimited_atom(&mut self) -> Result<Option<Node>, String> {
+        let node = match self.next() {
+            Token::End => return Ok(None),
+            Token::Char('{') => Node::row(self.row(Stop::Group)?),
+            Token::Char(c) if c.is_ascii_digit() || c == '.' && self.peek_digit() => {
+                let start = self.pos - 1;
+                while let Some(c) = self.s[self.pos..].chars().next() && (c.is_ascii_digit() || c == '.' && self.peek_digit_at(self.pos + 1)) {
+                    self.pos += 1;
+                }
+                Node::new(format!("<mn>{}</mn>", &self.s[start..self.pos]))
+            },
+            Token::Char(c) if c.is_alphabetic() => Node::new(format!("<mi>{c}</mi>")),
+            Token::Char('~') => Node::new(String::from(r#"<mspace width="0.25em"></mspace>"#)),
+            Token::Char('-') => operator("−"),
+            Token::Char('*') => operator("∗"),
+            Token::Char(c) => operator(&c.to_string()),
+            Token::Command(name) => return self.command(name),
+        };
+        Ok(Some(node))
+    }
+
+    fn peek_digit(&self) -> bool {
+        self.peek_digit_at(self.pos)
+    }
+
+    fn peek_digit_at(&self, pos: usize) -> bool {
+        self.s[pos..].starts_with(|c: char| c.is_ascii_digit())
+    }
+
+    fn command(&mut self, name: &'a str) -> Result<Option<Node>, String> {
+        if let Some((_, text, kind)) = SYMBOLS.iter().find(|v| v.0 == name) {
+            return Ok(Some(symbol(text, *kind)));
+        }
+        if let Some((_, accent, over)) = ACCENTS.iter().find(|v| v.0 == name) {
+            let base = self.argument()?;
+            let node = match over {
+                true => Node::new(format!(r#"<mover accent="true">{}<mo stretchy="true">{accent}</mo></mover>"#, base.xml)),
+                false => Node::new(format!(r#"<munder accentunder="true">{}<mo stretchy="true">{accent}</mo></munder>"#, base.xml)),
+            };
+            return Ok(Some(node));
+        }
+        if let Some((_, width)) = SPACES.iter().find(|v| v.0 == name) {
+            return Ok(Some(Node::new(format!(r#"<mspace width="{width}"></mspace>"#))));
+        }
+
+        let node = match name {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => {
+                let (a, b) = (self.argument()?, self.argument()?);
+                Node::new(format!("<mfrac>{}{}</mfrac>", a.xml, b.xml))
+            },
+            "binom" => {
+                let (a, b) = (self.argument()?, self.argument()?);
+                Node::new(format!(r#"<mrow><mo>(</mo><mfrac linethickness="0">{}{}</mfrac><mo>)</mo></mrow>"#, a.xml, b.xml))
+            },
+            "sqrt" => match self.peek() {
+                Token::Char('[') => {
+                    self.next();
+                    let mut index = Vec::new();
+                    while self.peek() != Token::Char(']') {
+                        match self.scripted()? {
+                            Some(node) => index.push(node),
+                            None => return Err(String::from("missing \"]\"")),
+                        }
+                    }
+                    self.next();
+                    let base = self.argument()?;
+                    Node::new(format!("<mroot>{}{}</mroot>", base.xml, Node::row(index).xml))
+                },
+                _ => Node::new(format!("<msqrt>{}</msqrt>", self.argument()?.xml)),
+            },
+            "text" | "textrm" | "textit" | "textbf" | "mbox" => {
+                let mut xml = String::from("<mtext>");
+                escape_html_body_text(&mut xml, self.braced_text()?).map_err(|_| String::from("can't write"))?;
+                xml.push_str("</mtext>");
+                Node::new(xml)
+            },
+            "operatorname" => {
+                let mut xml = String::from("<mi>");
+                escape_html_body_text(&mut xml, self.braced_text()?).map_err(|_| String::from("can't write"))?;
+                xml.push_str("</mi>");
+                Node::new(xml)
+            },
+            "mathrm" | "mathbf" | "mathit" | "mathbb" | "mathcal" | "mathfrak" | "mathsf" | "mathtt" | "boldsymbol" => {
+                let start = self.pos;
+                let text = self.braced_text()?;
+                match text.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ') {
+                    true => styled(name, text),
+                    false => {
+                        self.pos = start;
+                        self.group()?
+                    },
+                }
+            },
+            "overset" | "stackrel" | "underset" => {
+                let (script, base) = (self.argument()?, self.argument()?);
+                match name {
+                    "underset" => Node::new(format!("<munder>{}{}</munder>", base.xml, script.xml)),
+                    _ => Node::new(format!("<mover>{}{}</mover>", base.xml, script.xml)),
+                }
+            },
+            "left" => {
+                let open = self.delimiter()?;
+                let inner = self.row(Stop::Right)?;
+                self.next();
+                let close = self.delimiter()?;
+                let mut xml = String::from("<mrow>");
+                [open.as_str()].into_iter().chain(inner.iter().map(|v| v.xml.as_str())).chain([close.as_str()]).for_each(|v| xml.push_str(v));
+                xml.push_str("</mrow>");
+                Node::new(xml)
+            },
+            "middle" => Node::new(self.delimiter()?),
+            "big" | "Big" | "bigg" | "Bigg" | "bigl" | "bigr" | "Bigl" | "Bigr" | "biggl" | "biggr" | "Biggl" | "Biggr" => {
+                Node::new(self.delimiter()?.replace(r#" stretchy="true""#, ""))
+            },
+            "pmod" => Node::new(format!(r#"<mrow><mspace width="0.5em"></mspace><mo>(</mo><mo>mod</mo>{}<mo>)</mo></mrow>"#, self.argument()?.xml)),
+            "not" => match self.atom()? {
+                Some(node) if node.xml.starts_with("<mo>") => Node::new(node.xml.replacen("</mo>", "\u{338}</mo>", 1)),
+                _ => return Err(String::from("\"\\not\" expects a relation")),
+            },
+            "begin" => self.environment()?,
+            "limits" | "nolimits" | "displaystyle" | "textstyle" | "!" => return Ok(None),
+            "{" | "}" | "|" | "#" | "%" | "$" | "&" | "_" => operator(match name { "|" => "‖", name => name }),
+            name => return Err(format!("unknown command \"\\{name}\"")),
+        };
+        Ok(Some(node))
+    }
+
+    /// Stretchy `<mo>` of "\left", "\right" and "\big" delimiters, empty for "."
+    fn delimiter(&mut self) -> Result<String, String> {
+        let text = match self.next() {
+            Token::Char('.') => return Ok(String::new()),
+            Token::Char(c @ ('(' | ')' | '[' | ']' | '|' | '/')) => c.to_string(),
+            Token::Char('<') => String::from("⟨"),
+            Token::Char('>') => String::from("⟩"),
+            Token::Command("{") => String::from("{"),
+            Token::Command("}") => String::from("}"),
+            Token::Command("|") => String::from("‖"),
+            Token::Command(name) => match SYMBOLS.iter().find(|v| v.0 == name && v.2 == Kind::Op) {
+                Some((_, text, _)) => text.to_string(),
+                None => return Err(format!("unknown delimiter \"\\{name}\"")),
+            },
+            _ => return Err(String::from("missing delimiter")),
+        };
+        Ok(format!(r#"<mo stretchy="true">{}</mo>"#, escape(&text)))
+    }
+
+    /// "\begin{name}" already consumed up to the name, cells are separated by "&" and rows by "\\"
+    fn environment(&mut self) -> Result<Node, String> {
+        let name = self.braced_text()?;
+        let Some((_, open, close)) = ENVIRONMENTS.iter().find(|v| v.0 == name) else {
+            return Err(format!("unknown environment \"{name}\""));
+        };
+        if name == "array" {
+            self.braced_text()?;
+        }
+
+        let align = match name {
+            "cases" => r#" columnalign="left""#,
+            "aligned" | "align" | "align*" => r#" columnalign="right left""#,
+            _ => "",
+        };
+        let mut xml = format!("<mtable{align}><mtr><mtd>");
+        loop {
+            self.row(Stop::Cell)?.into_iter().for_each(|v| xml.push_str(&v.xml));
+            match self.next() {
+                Token::Char('&') => xml.push_str("</mtd><mtd>"),
+                Token::Command("\\") => xml.push_str("</mtd></mtr><mtr><mtd>"),
+                _ => {
+                    if self.braced_text()? != name {
+                        return Err(format!("\"\\begin{{{name}}}\" is closed by another environment"));
+                    }
+                    break;
+                },
+            }
+        }
+        xml.push_str("</mtd></mtr></mtable>");
+        let xml = xml.replace("<mtr><mtd></mtd></mtr>", "");
+
+        Ok(Node::new(match (*open, *close) {
+            ("", "") => xml,
+            (open, close) => {
+                let close = match close {
+                    "" => String::new(),
+                    close => format!(r#"<mo stretchy="true">{close}</mo>"#),
+                };
+                format!(r#"<mrow><mo stretchy="true">{open}</mo>{xml}{close}</mrow>"#)
+            },
+        }))
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn operator(text: &str) -> Node {
+    Node::new(format!("<mo>{}</mo>", escape(text)))
+}
+
+fn symbol(text: &str, kind: Kind) -> Node {
+    match kind {
+        Kind::Ident => Node::new(format!("<mi>{text}</mi>")),
+        Kind::Upright => Node::new(format!(r#"<mi mathvariant="normal">{text}</mi>"#)),
+        Kind::Op => operator(text),
+        Kind::Large => Node { xml: format!(r#"<mo largeop="true" movablelimits="true">{text}</mo>"#), limits: true },
+        Kind::Integral => Node::new(format!(r#"<mo largeop="true">{text}</mo>"#)),
+        Kind::Func => Node::new(format!("<mi>{text}</mi>")),
+        Kind::Limit => Node { xml: format!("<mi>{text}</mi>"), limits: true },
+    }
+}
+
+/// Letters and digits of "\mathbb{R}" and similar mapped to Mathematical Alphanumeric Symbols
+fn styled(command: &str, text: &str) -> Node {
+    let map = |c: char| -> char {
+        let exception = match (command, c) {
+            ("mathbb", 'C') => Some('ℂ'), ("mathbb", 'H') => Some('ℍ'), ("mathbb", 'N') => Some('ℕ'),
+            ("mathbb", 'P') => Some('ℙ'), ("mathbb", 'Q') => Some('ℚ'), ("mathbb", 'R') => Some('ℝ'),
+            ("mathbb", 'Z') => Some('ℤ'),
+            ("mathcal", 'B') => Some('ℬ'), ("mathcal", 'E') => Some('ℰ'), ("mathcal", 'F') => Some('ℱ'),
+            ("mathcal", 'H') => Some('ℋ'), ("mathcal", 'I') => Some('ℐ'), ("mathcal", 'L') => Some('ℒ'),
+            ("mathcal", 'M') => Some('ℳ'), ("mathcal", 'R') => Some('ℛ'), ("mathcal", 'e') => Some('ℯ'),
+            ("mathcal", 'g') => Some('ℊ'), ("mathcal", 'o') => Some('ℴ'),
+            ("mathfrak", 'C') => Some('ℭ'), ("mathfrak", 'H') => Some('ℌ'), ("mathfrak", 'I') => Some('ℑ'),
+            ("mathfrak", 'R') => Some('ℜ'), ("mathfrak", 'Z') => Some('ℨ'),
+            ("mathit", 'h') => Some('ℎ'),
+            _ => None,
+        };
+        // (capital A, small a, digit 0) of each alphabet
+        let base = match command {
+            "mathbf" | "boldsymbol" => Some((0x1D400, 0x1D41A, Some(0x1D7CE))),
+            "mathit"                => Some((0x1D434, 0x1D44E, None)),
+            "mathbb"                => Some((0x1D538, 0x1D552, Some(0x1D7D8))),
+            "mathcal"               => Some((0x1D49C, 0x1D4B6, None)),
+            "mathfrak"              => Some((0x1D504, 0x1D51E, None)),
+            "mathsf"                => Some((0x1D5A0, 0x1D5BA, Some(0x1D7E2))),
+            "mathtt"                => Some((0x1D670, 0x1D68A, Some(0x1D7F6))),
+            _                       => None,
+        };
+        let code = match (base, c) {
+            (Some((upper, _, _)), 'A'..='Z') => char::from_u32(upper + (c as u32 - 'A' as u32)),
+            (Some((_, lower, _)), 'a'..='z') => char::from_u32(lower + (c as u32 - 'a' as u32)),
+            (Some((_, _, Some(digit))), '0'..='9') => char::from_u32(digit + (c as u32 - '0' as u32)),
+            _ => None,
+        };
+        exception.or(code).unwrap_or(c)
+    };
+
+    let text = text.split_whitespace().collect::<String>();
+    match command {
+        "mathrm" if text.chars().count() == 1 => Node::new(format!(r#"<mi mathvariant="normal">{text}</mi>"#)),
+        "mathrm" => Node::new(format!("<mi>{text}</mi>")),
+        _ => Node::row(text.chars().map(|c| match c.is_ascii_digit() {
+            true => Node::new(format!("<mn>{}</mn>", map(c))),
+            false => Node::new(format!(r#"<mi mathvariant="normal">{}</mi>"#, map(c))),
+        }).collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inner(tex: &str, display: bool) -> String {
+        let s = render(tex, display).unwrap();
+        let start = s.find("<semantics><mrow>").unwrap() + "<semantics><mrow>".len();
+        let end = s.rfind("</mrow><annotation").unwrap();
+        s[start..end].to_string()
+    }
+
+    #[test]
+    fn test_render() {
+        [
+            ("x^2 + 1",                 "<msup><mi>x</mi><mn>2</mn></msup><mo>+</mo><mn>1</mn>"),
+            ("a_{ij} - 3.14",           "<msub><mi>a</mi><mrow><mi>i</mi><mi>j</mi></mrow></msub><mo>−</mo><mn>3.14</mn>"),
+            ("x_1^2",                   "<msubsup><mi>x</mi><mn>1</mn><mn>2</mn></msubsup>"),
+            ("f'",                      "<msup><mi>f</mi><mo>′</mo></msup>"),
+            ("f'^2",                    "<msup><mi>f</mi><mrow><mo>′</mo><mn>2</mn></mrow></msup>"),
+            ("\\frac{a}{b} < c",        "<mfrac><mi>a</mi><mi>b</mi></mfrac><mo>&lt;</mo><mi>c</mi>"),
+            ("\\sqrt[3]{x}",            "<mroot><mi>x</mi><mn>3</mn></mroot>"),
+            ("\\alpha \\leq \\Omega",   "<mi>α</mi><mo>≤</mo><mi mathvariant=\"normal\">Ω</mi>"),
+            ("\\sin x",                 "<mi>sin</mi><mi>x</mi>"),
+            ("\\mathbb{R}^n",           "<msup><mi mathvariant=\"normal\">ℝ</mi><mi>n</mi></msup>"),
+            ("\\text{if } x",           "<mtext>if </mtext><mi>x</mi>"),
+            ("\\hat{x}",                "<mover accent=\"true\"><mi>x</mi><mo stretchy=\"true\">^</mo></mover>"),
+            ("\\left( x \\right.",      "<mrow><mo stretchy=\"true\">(</mo><mi>x</mi></mrow>"),
+            ("\\not\\equiv",            "<mo>≡\u{338}</mo>"),
+            ("\\begin{pmatrix}1 & 2 \\\\ 3 & 4\\end{pmatrix}",
+             "<mrow><mo stretchy=\"true\">(</mo><mtable><mtr><mtd><mn>1</mn></mtd><mtd><mn>2</mn></mtd></mtr><mtr><mtd><mn>3</mn></mtd><mtd><mn>4</mn></mtd></mtr></mtable><mo stretchy=\"true\">)</mo></mrow>"),
+        ].into_iter().for_each(|(tex, expected)| assert_eq!(inner(tex, false), expected, "TeX: {tex}"));
+
+        assert_eq!(
+            inner("\\sum_{i=1}^n i", true),
+            "<munderover><mo largeop=\"true\" movablelimits=\"true\">∑</mo><mrow><mi>i</mi><mo>=</mo><mn>1</mn></mrow><mi>n</mi></munderover><mi>i</mi>",
+        );
+        assert!(inner("\\sum_{i=1}^n i", false).starts_with("<msubsup>"));
+        assert!(inner("\\int\\limits_0^1", true).starts_with("<munderover>"));
+
+        let block = render("a < b", true).unwrap();
+        assert!(block.starts_with("<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\" class=\"math math-display\">"), "{block}");
+        assert!(block.ends_with("<annotation encoding=\"application/x-tex\">a &lt; b</annotation></semantics></math>"), "{block}");
+    }
+
+    #[test]
+    fn test_errors() {
+        [
+            ("\\unknown",               "unknown command \"\\unknown\""),
+            ("{x",                      "missing \"}\" or \"\\right\""),
+            ("x}",                      "unexpected \"}\""),
+            ("x^",                      "missing argument"),
+            ("x^1^2",                   "double script, use braces"),
+            ("\\begin{foo}\\end{foo}",  "unknown environment \"foo\""),
+            ("\\left( x",               "missing \"}\" or \"\\right\""),
+        ].into_iter().for_each(|(tex, expected)| assert_eq!(render(tex, false).unwrap_err(), expected, "TeX: {tex}"));
+
+        for tex in ["{".repeat(100_000), "\\sqrt".repeat(100_000), "x^{".repeat(100_000), "\\left(".repeat(100_000)] {
+            assert_eq!(render(&tex, false).unwrap_err(), "nested deeper than 100 levels");
+        }
+        assert!(render(&format!("{}x{}", "{".repeat(99), "}".repeat(99)), false).is_ok());
+    }
+}
@@ -0,0 +1,84 @@
+// Search over "searchIndex" from "search-index.js", every query word has to be a prefix
+// of a word in a document, results are ordered by the sum of matched word weights
+(function () {
+    "use strict";
+
+    var input = document.getElementById("search-input");
+    var results = document.getElementById("search-results");
+    var status = document.getElementById("search-status");
+    if (!input || !results || typeof searchIndex === "undefined") {
+        return;
+    }
+
+    function tokens(s) {
+        return s.toLowerCase().split(/[^\p{Alphabetic}\p{N}]+/u).filter(function (v) {
+            return Array.from(v).length > 1;
+        });
+    }
+
+    function search(query) {
+        var scores = null;
+        tokens(query).forEach(function (token) {
+            var found = {};
+            searchIndex.words.forEach(function (entry) {
+                if (entry[0].lastIndexOf(token, 0) !== 0) {
+                    return;
+                }
+                for (var i = 0; i < entry[1].length; i += 2) {
+                    found[entry[1][i]] = (found[entry[1][i]] || 0) + entry[1][i + 1];
+                }
+            });
+            if (scores === null) {
+                scores = found;
+                return;
+            }
+            Object.keys(scores).forEach(function (doc) {
+                if (doc in found) {
+                    scores[doc] += found[doc];
+                } else {
+                    delete scores[doc];
+                }
+            });
+        });
+
+        return Object.keys(scores || {}).sort(function (a, b) {
+            return scores[b] - scores[a] || a - b;
+        }).map(function (doc) {
+            return searchIndex.docs[doc];
+        });
+    }
+
+    function element(name, className, text) {
+        var node = document.createElement(name);
+        node.className = className;
+        node.textContent = text;
+        return node;
+    }
+
+    function render() {
+        var query = input.value.trim();
+        var found = search(query);
+        results.textContent = "";
+        found.forEach(function (doc) {
+            var item = document.createElement("li");
+            var link = element("a", "search-title", doc[0]);
+            link.href = doc[1];
+            item.appendChild(link);
+            item.appendChild(document.createTextNode(" "));
+            item.appendChild(element("time", "search-date", doc[2]));
+            item.appendChild(element("p", "search-snippet", doc[3]));
+            results.appendChild(item);
+        });
+        if (status) {
+            status.textContent = query ? found.length + " result(s)" : "";
+        }
+        if (window.history && window.history.replaceState && location.protocol !== "file:") {
+            window.history.replaceState(null, "", query ? "?q=" + encodeURIComponent(query) : location.pathname);
+        }
+    }
+
+    var params = new URLSearchParams(location.search);
+    input.value = params.get("q") || "";
+    input.addEventListener("input", render);
+    render();
+})();
@@ -0,0 +1,88 @@
+// INFO Client-side search, "search-index.js" holds documents and an inverted index of their words,
+// "search.js" matches query words as prefixes of indexed ones, a document must contain every word
+
+use std::collections::BTreeMap;
+use crate::article::Article;
+use crate::markdown::Extensions;
+
+/// Loaded by "search.html" before "search.js", a script instead of JSON so it works from "file://" too
+pub const INDEX: &str = "search-index.js";
+pub const SCRIPT: &str = "search.js";
+pub const SCRIPT_SOURCE: &str = include_str!("search.js");
+
+/// Weight of a word in a title or a term, a word of the text has weight 1
+const TITLE_WEIGHT: u32 = 10;
+const TERM_WEIGHT: u32 = 5;
+
+/// Characters of a description or text shown in results
+const SNIPPET: usize = 160;
+
+#[derive(serde::Serialize)]
+struct Index<'a> {
+    /// (title, path, date, snippet)
+    docs:  Vec<(&'a str, &'a str, String, String)>,
+    /// (word, [document, weight, document, weight, ...])
+    words: Vec<(String, Vec<u32>)>,
+}
+
+/// Script assigning the index of `articles` to `searchIndex`
+pub fn index(articles: &[&(Article, String)], extensions: Extensions) -> String {
+    let mut docs = Vec::with_capacity(articles.len());
+    let mut words = BTreeMap::<String, BTreeMap<u32, u32>>::new();
+
+    for (i, (article, path)) in articles.iter().map(|v| (&v.0, &v.1)).enumerate() {
+        let text = crate::markdown::text(article.body().unwrap_or_default(), extensions.with(&article.markdown));
+        let terms = article.tags.iter().chain(&article.categories).map(String::as_str);
+        let sources = [(article.name(), TITLE_WEIGHT), (article.description.as_deref().unwrap_or_default(), 1), (&text, 1)]
+            .into_iter()
+            .chain(terms.map(|v| (v, TERM_WEIGHT)));
+
+        for (s, weight) in sources {
+            for word in tokens(s) {
+                *words.entry(word).or_default().entry(i as u32).or_default() += weight;
+            }
+        }
+
+        let snippet = article.description.as_deref().unwrap_or(&text).split_whitespace().collect::<Vec<_>>().join(" ");
+        let snippet = match snippet.char_indices().nth(SNIPPET) {
+            Some((end, _)) => format!("{}…", snippet[..end].trim_end()),
+            None => snippet,
+        };
+        docs.push((article.name(), path.as_str(), article.ts.to_string(), snippet));
+    }
+
+    let words = words.into_iter().map(|(word, docs)| (word, docs.into_iter().flat_map(|(i, n)| [i, n]).collect())).collect();
+    let json = serde_json::to_string(&Index { docs, words }).unwrap_or_default();
+    format!("var searchIndex = {json};\n")
+}
+
+/// Lowercase words of two or more letters or digits, the same split as in "search.js"
+fn tokens(s: &str) -> impl Iterator<Item = String> {
+    s.split(|c: char| !c.is_alphanumeric()).filter(|v| v.chars().nth(1).is_some()).map(str::to_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index() {
+        let articles = [
+            "---\ntitle: Rust tips\ndate: 2026-02-04\ntags: [Perf]\n---\nFast `Vec` code, a rust-y one.",
+            "---\ntitle: Other\ndate: 2026-02-05\ndescription: About \"quotes\"\n---\nNo match here.",
+        ].map(|s| (Article::new(s.to_string()).unwrap(), String::from("a.html")));
+        let result = index(&articles.iter().collect::<Vec<_>>(), Extensions::default());
+
+        let json = result.strip_prefix("var searchIndex = ").and_then(|v| v.strip_suffix(";\n")).unwrap();
+        let value: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(value["docs"][0], serde_json::json!(["Rust tips", "a.html", "04 feb 2026", "Fast Vec code, a rust-y one."]));
+        assert_eq!(value["docs"][1][3], "About \"quotes\"");
+
+        let words = value["words"].as_array().unwrap();
+        let find = |word: &str| words.iter().find(|v| v[0] == word).map(|v| v[1].clone());
+        assert_eq!(find("rust"), Some(serde_json::json!([0, 11])));
+        assert_eq!(find("perf"), Some(serde_json::json!([0, 5])));
+        assert_eq!(find("vec"), Some(serde_json::json!([0, 1])));
+        assert_eq!(find("a"), None);
+    }
+}
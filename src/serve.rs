@@ -0,0 +1,207 @@
+use std::io::{ BufRead as _, Write as _ };
+use std::path::{ Path, PathBuf };
+
+const RELOAD_PATH: &str = "/__livereload";
+const RELOAD_SCRIPT: &str = "<script>new EventSource(\"/__livereload\").onmessage = () => location.reload();</script>";
+
+static CLIENTS: std::sync::Mutex<Vec<std::net::TcpStream>> = std::sync::Mutex::new(Vec::new());
+
+/// Serves `output` on localhost and rebuilds the site, by running the same binary
+/// with the same options minus the "serve" subcommand, whenever a watched path changes
+pub fn run(output: PathBuf, watch: Vec<PathBuf>, port: u16) -> ! {
+    let args = std::env::args_os().skip(1).collect::<Vec<_>>();
+    let args = args[..args.iter().rposition(|v| v == "serve").unwrap_or(args.len())].to_vec();
+
+    let listener = std::net::TcpListener::bind(("127.0.0.1", port))
+        .unwrap_or_else(|e| die!("Error: can't listen on 127.0.0.1:{port} - {e}"));
+
+    rebuild(&args);
+    println!("Info: serving {output:?} on http://127.0.0.1:{port}/, press Ctrl+C to stop");
+
+    std::thread::spawn(move || {
+        let mut state = fingerprint(&watch);
+        loop {
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            let next = fingerprint(&watch);
+            if next != state {
+                state = next;
+                if rebuild(&args) {
+                    CLIENTS.lock().unwrap().retain_mut(|client| client.write_all(b"data: reload\n\n").is_ok());
+                }
+            }
+        }
+    });
+
+    for stream in listener.incoming().flatten() {
+        let output = output.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = respond(stream, &output) {
+                println!("Warning: request failed - {e}");
+            }
+        });
+    }
+
+    die!("Error: server stopped");
+}
+
+fn rebuild(args: &[std::ffi::OsString]) -> bool {
+    let status = std::env::current_exe().and_then(|exe| std::process::Command::new(exe).args(args).status());
+    match status {
+        Ok(status) if status.success() => true,
+        Ok(status) => { println!("Warning: build failed with {status}, waiting for changes"); false },
+        Err(e) => { println!("Warning: can't run build - {e}"); false },
+    }
+}
+
+fn fingerprint(paths: &[PathBuf]) -> u64 {
+    use std::hash::{ Hash as _, Hasher as _ };
+
+    fn visit(path: &Path, hasher: &mut std::hash::DefaultHasher) {
+        let Ok(meta) = std::fs::metadata(path) else {
+            return;
+        };
+        path.hash(hasher);
+        if meta.is_dir() {
+            let mut entries = std::fs::read_dir(path).into_iter().flatten().flatten().map(|v| v.path()).collect::<Vec<_>>();
+            entries.sort_unstable();
+            entries.iter().for_each(|v| visit(v, hasher));
+        } else {
+            meta.len().hash(hasher);
+            meta.modified().ok().hash(hasher);
+        }
+    }
+
+    let mut hasher = std::hash::DefaultHasher::new();
+    paths.iter().for_each(|v| visit(v, &mut hasher));
+    hasher.finish()
+}
+
+fn respond(mut stream: std::net::TcpStream, output: &Path) -> Result<(), std::io::Error> {
+    let mut reader = std::io::BufReader::new(stream.try_clone()?);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+    if method != "GET" && method != "HEAD" {
+        return stream.write_all(b"HTTP/1.1 405 Method Not Allowed\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+    }
+
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+    if path == RELOAD_PATH {
+        stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n")?;
+        CLIENTS.lock().unwrap().push(stream);
+        return Ok(());
+    }
+
+    let file = resolve(output, path).and_then(|v| std::fs::read(&v).ok().map(|data| (v, data)));
+    let (status, content_type, body) = match file {
+        Some((file, data)) => match content_type(&file) {
+            "text/html; charset=utf-8" => ("200 OK", "text/html; charset=utf-8", inject(data)),
+            content_type => ("200 OK", content_type, data),
+        },
+        None => ("404 Not Found", "text/html; charset=utf-8", inject(format!("<h1>404</h1><p>{path} not found</p>").into_bytes())),
+    };
+
+    write!(stream, "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n", body.len())?;
+    if method == "GET" {
+        stream.write_all(&body)?;
+    }
+    stream.flush()
+}
+
+fn resolve(output: &Path, path: &str) -> Option<PathBuf> {
+    let path = decode(path)?;
+    let mut result = output.to_path_buf();
+    for segment in path.split('/').filter(|v| !v.is_empty() && *v != ".") {
+        if segment == ".." || segment.contains('\\') {
+            return None;
+        }
+        result.push(segment);
+    }
+    if result.is_dir() {
+        result.push("index.html");
+    }
+    result.is_file().then_some(result)
+}
+
+fn decode(s: &str) -> Option<String> {
+    let mut result = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'%' => {
+                let hex = [bytes.next()?, bytes.next()?];
+                result.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            },
+            b => result.push(b),
+        }
+    }
+    String::from_utf8(result).ok()
+}
+
+fn inject(mut html: Vec<u8>) -> Vec<u8> {
+    let position = html.windows(7).rposition(|v| v.eq_ignore_ascii_case(b"</body>")).unwrap_or(html.len());
+    html.splice(position..position, RELOAD_SCRIPT.bytes());
+    html
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|v| v.to_str()).map(str::to_ascii_lowercase).as_deref() {
+        Some("html" | "htm")  => "text/html; charset=utf-8",
+        Some("css")           => "text/css; charset=utf-8",
+        Some("js" | "mjs")    => "text/javascript; charset=utf-8",
+        Some("json")          => "application/json",
+        Some("xml")           => "application/xml",
+        Some("txt" | "md")    => "text/plain; charset=utf-8",
+        Some("svg")           => "image/svg+xml",
+        Some("png")           => "image/png",
+        Some("jpg" | "jpeg")  => "image/jpeg",
+        Some("gif")           => "image/gif",
+        Some("webp")          => "image/webp",
+        Some("avif")          => "image/avif",
+        Some("ico")           => "image/x-icon",
+        Some("woff")          => "font/woff",
+        Some("woff2")         => "font/woff2",
+        Some("wasm")          => "application/wasm",
+        _                     => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("/tags/c%2B%2B.html").as_deref(), Some("/tags/c++.html"));
+        assert_eq!(decode("/tags/%D1%97.html").as_deref(), Some("/tags/ї.html"));
+        assert_eq!(decode("/bad%2"), None);
+        assert_eq!(decode("/bad%FF"), None);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let output = crate::test_utils::TempDir::new("serve");
+        std::fs::create_dir_all(output.join("tags")).unwrap();
+        std::fs::write(output.join("index.html"), "").unwrap();
+        std::fs::write(output.join("tags").join("index.html"), "").unwrap();
+
+        assert_eq!(resolve(&output, "/"), Some(output.join("index.html")));
+        assert_eq!(resolve(&output, "/tags/"), Some(output.join("tags").join("index.html")));
+        assert_eq!(resolve(&output, "/./index.html"), Some(output.join("index.html")));
+        assert_eq!(resolve(&output, "/../index.html"), None);
+        assert_eq!(resolve(&output, "/%2E%2E/index.html"), None);
+        assert_eq!(resolve(&output, "/missing.html"), None);
+    }
+
+    #[test]
+    fn test_inject() {
+        assert_eq!(inject(b"<body>a</BODY></html>".to_vec()), format!("<body>a{RELOAD_SCRIPT}</BODY></html>").into_bytes());
+        assert_eq!(inject(b"<p>a</p>".to_vec()), format!("<p>a</p>{RELOAD_SCRIPT}").into_bytes());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,156 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// Configuration files looked up in the working directory when "--config" isn't given
+pub const FILES: &[&str] = &["site.toml", "blog.toml"];
+
+/// Values available to templates as `site`
+#[derive(Debug, serde::Serialize)]
+pub struct Site {
+    pub name:     String,
+    pub base_url: Option<String>,
+    pub language: String,
+    pub author:   Option<String>,
+}
+
+/// Sets options of the configuration file as defaults of `command`, so options given
+/// on the command line override them and the command line is parsed as is.
+/// Keys are long option names, "per_page = 10" is the same as "--per-page 10",
+/// tables aren't supported since every option is top-level
+pub fn command(mut command: clap::Command, args: &[OsString]) -> Result<clap::Command, String> {
+    let explicit = command.clone().ignore_errors(true).try_get_matches_from(args).ok()
+        .and_then(|v| v.get_one::<PathBuf>("config").cloned());
+
+    let path = match explicit {
+        Some(path) => path,
+        None => match FILES.iter().map(PathBuf::from).find(|v| v.is_file()) {
+            Some(path) => {
+                command = command.mut_arg("config", |v| v.default_value(path.clone().into_os_string()));
+                path
+            },
+            None => return Ok(command),
+        },
+    };
+
+    let s = std::fs::read_to_string(&path).map_err(|e| format!("{path:?} - {e}"))?;
+    let table = s.parse::<toml::Table>().map_err(|e| format!("{path:?} - {}", e.message()))?;
+
+    for (key, value) in table {
+        let long = key.replace('_', "-");
+        let arg = command.get_arguments()
+            .filter(|v| !matches!(v.get_id().as_str(), "config" | "help" | "version"))
+            .find(|v| v.get_long() == Some(long.as_str()))
+            .ok_or_else(|| format!("{path:?} - unknown key {key:?}"))?;
+
+        let values = match value {
+            toml::Value::Boolean(v) if !arg.get_action().takes_values() => vec![v.to_string()],
+            _ if !arg.get_action().takes_values() => return Err(format!("{path:?} - {key:?} expects true or false")),
+            toml::Value::Array(list) if arg.get_num_args().is_some_and(|v| v.max_values() > 1) => {
+                list.into_iter().map(scalar).collect::<Option<Vec<_>>>().ok_or_else(|| format!("{path:?} - {key:?} expects a list of values"))?
+            },
+            toml::Value::Array(_) => return Err(format!("{path:?} - {key:?} expects a single value")),
+            value => vec![scalar(value).ok_or_else(|| format!("{path:?} - {key:?} expects a value, tables aren't supported"))?],
+        };
+        let values = match arg.get_value_delimiter() {
+            Some(delimiter) => values.iter().flat_map(|v| v.split(delimiter)).map(String::from).collect(),
+            None => values,
+        };
+        if arg.get_action().takes_values() {
+            clap::Command::new("config").no_binary_name(true).arg(arg.clone())
+                .try_get_matches_from(std::iter::once(format!("--{long}")).chain(values.iter().cloned()))
+                .map_err(|e| format!("{path:?} - {key:?} {}", e.kind()))?;
+        }
+
+        let id = arg.get_id().clone();
+        command = command.mut_arg(id, |v| v.default_values(values));
+    }
+
+    println!("Info: {path:?} loaded");
+    Ok(command)
+}
+
+fn scalar(value: toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(v) => Some(v),
+        toml::Value::Integer(v) => Some(v.to_string()),
+        toml::Value::Float(v) => Some(v.to_string()),
+        toml::Value::Boolean(v) => Some(v.to_string()),
+        toml::Value::Datetime(v) => Some(v.to_string()),
+        toml::Value::Array(_) | toml::Value::Table(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(clap::Parser, Debug)]
+    #[command(args_override_self = true)]
+    struct Test {
+        #[arg(long)]
+        config: Option<PathBuf>,
+        #[arg(long, default_value = "a")]
+        name: String,
+        #[arg(long)]
+        per_page: Option<usize>,
+        #[arg(long)]
+        drafts: bool,
+        #[arg(long, num_args = 1..)]
+        files: Option<Vec<String>>,
+        #[arg(long, value_delimiter = ',', num_args = 1.., default_values = ["tables"])]
+        markdown: Vec<String>,
+        #[command(subcommand)]
+        command: Option<Sub>,
+    }
+
+    #[derive(clap::Subcommand, Debug, PartialEq)]
+    enum Sub {
+        Serve {
+            #[arg(short, long, default_value_t = 8080)]
+            port: u16,
+        },
+        Theme {
+            #[arg(default_value = ".")]
+            dir: PathBuf,
+        },
+    }
+
+    #[test]
+    fn test_command() {
+        use clap::{ CommandFactory as _, FromArgMatches as _ };
+
+        let dir = crate::test_utils::TempDir::new("site");
+        let path = dir.join("site.toml");
+        let parse = |config: &str, cli: &[&str]| {
+            std::fs::write(&path, config).unwrap();
+            let mut input = vec![OsString::from("bin"), OsString::from("--config"), path.clone().into_os_string()];
+            input.extend(cli.iter().map(OsString::from));
+            command(Test::command(), &input).map(|v| Test::from_arg_matches(&v.get_matches_from(input)).unwrap())
+        };
+
+        let v = parse("name = \"b\"\nper_page = 5\ndrafts = true\nfiles = [\n  \"x\",\n  \"y\",\n]", &[]).unwrap();
+        assert_eq!((v.name.as_str(), v.per_page, v.drafts, v.files), ("b", Some(5), true, Some(vec!["x".into(), "y".into()])));
+        assert_eq!(v.config, Some(path.clone()));
+
+        let v = parse("name = \"b\"\nper_page = 5\ndrafts = false", &["--name", "c", "--drafts"]).unwrap();
+        assert_eq!((v.name.as_str(), v.per_page, v.drafts), ("c", Some(5), true));
+
+        // Lists of the file don't take subcommands and their arguments as values
+        let v = parse("markdown = [\"tables\", \"math\"]\nfiles = [\"x\"]", &["theme", "out"]).unwrap();
+        assert_eq!((v.markdown, v.files, v.command), (vec!["tables".into(), "math".into()], Some(vec!["x".into()]), Some(Sub::Theme { dir: "out".into() })));
+        let v = parse("markdown = [\"tables\", \"math\"]", &["serve", "-p", "1"]).unwrap();
+        assert_eq!((v.markdown, v.command), (vec!["tables".into(), "math".into()], Some(Sub::Serve { port: 1 })));
+        let v = parse("markdown = [\"tables\", \"math\"]", &["--markdown", "footnotes"]).unwrap();
+        assert_eq!(v.markdown, ["footnotes"]);
+        let v = parse("markdown = \"tables,math\"", &[]).unwrap();
+        assert_eq!(v.markdown, ["tables", "math"]);
+
+        assert!(parse("unknown = 1", &[]).is_err());
+        assert!(parse("config = \"x\"", &[]).is_err());
+        assert!(parse("drafts = 1", &[]).is_err());
+        assert!(parse("per_page = \"x\"", &[]).is_err());
+        assert!(parse("name = [\"a\", \"b\"]", &[]).is_err());
+        assert!(parse("[section]", &[]).is_err());
+        assert!(parse("name = ", &[]).is_err());
+    }
+}
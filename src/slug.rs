@@ -0,0 +1,37 @@
+pub fn slugify(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+
+    for c in s.trim().chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() || c == '_' {
+            result.push(c);
+        } else if !result.is_empty() && !result.ends_with('-') {
+            result.push('-');
+        }
+    }
+
+    if result.ends_with('-') {
+        result.pop();
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slugify() {
+        [
+            ("Rust",                 "rust"),
+            ("  Hello, World!  ",    "hello-world"),
+            ("snake_case stays",     "snake_case-stays"),
+            ("C++ / C#",             "c-c"),
+            ("Non ascїї Заголовок",  "non-ascїї-заголовок"),
+            ("---",                  ""),
+            ("",                     ""),
+        ].into_iter().for_each(|(input, expected)| {
+            assert_eq!(slugify(input), expected, "Input: {input:?}");
+        });
+    }
+}
\ No newline at end of file
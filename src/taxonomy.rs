@@ -0,0 +1,56 @@
+use crate::article::Article;
+use crate::slug::slugify;
+
+pub type Selector = fn(&Article) -> &[String];
+
+#[derive(serde::Serialize)]
+pub struct Term<'a> {
+    pub name:     &'a str,
+    pub slug:     String,
+    pub articles: Vec<&'a (Article, String)>,
+}
+
+/// Groups already sorted articles by term slug, keeping their order within each term,
+/// articles without a body have no page and are skipped, the same as in feeds
+pub fn collect<'a>(articles: &[&'a (Article, String)], terms: Selector) -> Vec<Term<'a>> {
+    let mut result = std::collections::BTreeMap::<String, Term<'a>>::new();
+
+    for &entry in articles.iter().filter(|v| v.0.body().is_some()) {
+        for name in terms(&entry.0) {
+            let slug = slugify(name);
+            let term = result.entry(slug.clone()).or_insert_with(|| Term { name, slug, articles: Vec::new() });
+            if !term.articles.iter().any(|v| std::ptr::eq(*v, entry)) {
+                term.articles.push(entry);
+            }
+        }
+    }
+
+    result.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect() {
+        let articles = [
+            "---\ntitle: A\ndate: 3\ntags: [Rust, perf]\n---\nText",
+            "---\ntitle: B\ndate: 2\ntags: [rust, rust]\n---\nText",
+            "---\ntitle: C\ndate: 1\n---\nText",
+            // Unpublished or loaded without a body, its only term has no page either
+            "---\ntitle: D\ndate: 0\ntags: [rust, unpublished]\n---\n",
+        ].map(|s| (Article::new(s.to_string()).unwrap(), String::new()));
+        let sorted = articles.iter().collect::<Vec<_>>();
+
+        let terms = collect(&sorted, |a| &a.tags)
+            .into_iter()
+            .map(|t| (t.name, t.slug, t.articles.iter().map(|a| a.0.name()).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(terms, [
+            ("perf", "perf".to_string(), vec!["A"]),
+            ("Rust", "rust".to_string(), vec!["A", "B"]),
+        ]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,189 @@
+// INFO Runtime overrides of the built-in templates, a file from `DEFAULTS` found in the templates
+// directory is rendered by minijinja instead of askama with the same context fields,
+// other files of the directory are available to "extends" and "include"
+
+use std::path::{ Path, PathBuf };
+use minijinja::value::ViaDeserialize;
+use minijinja::{ Environment, Error, State, Value };
+use crate::markdown::{ Extension, Markdown, Options };
+
+/// Built-in templates in minijinja syntax, the "templates" subcommand writes them as a starting point of overrides
+pub const DEFAULTS: &[(&str, &str)] = &[
+    ("article.html",  include_str!("../templates/minijinja/article.html")),
+    ("index.html",    include_str!("../templates/minijinja/index.html")),
+    ("taxonomy.html", include_str!("../templates/minijinja/taxonomy.html")),
+    ("term.html",     include_str!("../templates/minijinja/term.html")),
+    ("search.html",   include_str!("../templates/minijinja/search.html")),
+    ("atom.xml",      include_str!("../templates/minijinja/atom.xml")),
+    ("rss.xml",       include_str!("../templates/minijinja/rss.xml")),
+    ("sitemap.xml",   include_str!("../templates/minijinja/sitemap.xml")),
+];
+
+static RUNTIME: std::sync::OnceLock<(PathBuf, Environment<'static>, Vec<&'static str>)> = std::sync::OnceLock::new();
+
+/// Compiles overrides found in `dir`, returns their names
+pub fn load(dir: &Path) -> Result<Vec<&'static str>, String> {
+    let mut env = environment();
+    env.set_loader(minijinja::path_loader(dir));
+
+    let mut names = Vec::new();
+    for name in DEFAULTS.iter().map(|v| v.0).filter(|v| dir.join(v).is_file()) {
+        env.get_template(name).map_err(|e| describe(dir, &e))?;
+        names.push(name);
+    }
+
+    RUNTIME.set((dir.to_path_buf(), env, names.clone())).map_err(|_| String::from("templates are already loaded"))?;
+    Ok(names)
+}
+
+fn environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_keep_trailing_newline(true);
+    env.add_filter("slugify", |s: &str| crate::slug::slugify(s));
+    // INFO HTML is safe in HTML templates only, feeds get it escaped,
+    // switches are "markdown" of an article, e.g. `article.body|markdown(options, "", article.markdown)`
+    env.add_filter("markdown", |state: &State, s: &str, options: Option<ViaDeserialize<Options>>, base: Option<&str>, switches: Option<ViaDeserialize<Vec<(Extension, bool)>>>| {
+        let options = options.map(|v| v.0).unwrap_or_default().with(&switches.map(|v| v.0).unwrap_or_default());
+        let html = Markdown::new(s, options, base.unwrap_or_default()).to_string();
+        match state.name().ends_with(".html") {
+            true => Value::from_safe_string(html),
+            false => Value::from(html),
+        }
+    });
+    env
+}
+
+/// Renders the override of `name` if there is one
+pub fn render<T: serde::Serialize>(name: &str, context: &T) -> Option<Result<String, String>> {
+    let (dir, env, names) = RUNTIME.get()?;
+    if !names.contains(&name) {
+        return None;
+    }
+    Some(env.get_template(name).and_then(|v| v.render(context)).map_err(|e| describe(dir, &e)))
+}
+
+/// "templates/article.html:12 - undefined value (in article.html:12)", followed by the causes
+fn describe(dir: &Path, e: &Error) -> String {
+    let mut s = match (e.name(), e.line()) {
+        (Some(name), Some(line)) => format!("{}:{line} - {e}", dir.join(name).display()),
+        (Some(name), None) => format!("{} - {e}", dir.join(name).display()),
+        _ => format!("{} - {e}", dir.display()),
+    };
+    let mut source = std::error::Error::source(e);
+    while let Some(e) = source {
+        s.push_str(&format!(": {e}"));
+        source = e.source();
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::article::Article;
+
+    #[test]
+    fn test_context() {
+        let article = Article::new(String::from("---\ntitle: Hello\ndate: 2026-02-04 11:05\ntags: [Rust Lang]\n---\n# A & B")).unwrap();
+        let body = article.body().unwrap();
+        let context = minijinja::context! {
+            article => &article,
+            content => Markdown::new(body, Options::default(), ""),
+            text => "<b>",
+        };
+
+        let mut env = environment();
+        [
+            ("{{ article.name }} {{ article.ts }}",         "Hello 04 feb 2026"),
+            ("{{ article.ts.rfc3339() }}",                  "2026-02-04T11:05:00Z"),
+            ("{{ article.modified.unix }}",                 "1770203100"),
+            ("{{ article.tags[0]|slugify }}",               "rust-lang"),
+            ("{{ content }} {{ text }}",                    "<h1 id=\"a-b\">A &amp; B</h1> &lt;b&gt;"),
+            ("{{ article.body|markdown({\"anchors\": true}) }}", "<h1 id=\"a-b\">A &amp; B<a class=\"heading-anchor\" href=\"#a-b\">#</a></h1>"),
+            ("{{ \"![a](b.png)\"|markdown(none, \"x/\") }}",   "<p><img src=\"x/b.png\" alt=\"a\" /></p>"),
+        ].into_iter().for_each(|(source, expected)| {
+            env.add_template_owned("test.html", source).unwrap();
+            assert_eq!(env.get_template("test.html").unwrap().render(&context).unwrap(), expected, "Template: {source}");
+        });
+
+        env.add_template("test.xml", "<content>{{ \"*a*\"|markdown }}</content>").unwrap();
+        assert_eq!(env.get_template("test.xml").unwrap().render(&context).unwrap(), "<content>&lt;p&gt;&lt;em&gt;a&lt;&#x2f;em&gt;&lt;&#x2f;p&gt;</content>");
+    }
+
+    #[test]
+    fn test_defaults() {
+        use crate::{ markdown::Document, site::Site, taxonomy };
+
+        // INFO askama templates are minified and escape differently, so lines are trimmed and entities unified
+        fn check<T: askama::Template + serde::Serialize>(env: &Environment, name: &str, t: T) {
+            let normalize = |s: String| s.lines().map(str::trim).collect::<String>()
+                .replace("&#x2f;", "/").replace("&#x27;", "'").replace("&#39;", "'").replace("&#34;", "&quot;")
+                .replace("&#38;", "&amp;").replace("&#60;", "&lt;").replace("&#62;", "&gt;");
+            let expected = normalize(t.render().unwrap());
+            let result = normalize(env.get_template(name).unwrap().render(&t).unwrap());
+            assert_eq!(result, expected, "Template: {name}");
+        }
+
+        let mut env = environment();
+        DEFAULTS.iter().for_each(|(name, source)| env.add_template(name, source).unwrap());
+
+        let mut a = Article::new(String::from("---\ntitle: A & B\ndate: 2026-02-04\nupdated: 2026-02-05\ndescription: It's \"d\"\ntags: [Rust, C++ Lang]\ncategories: [Notes]\nauthor: Me\nmarkdown: [math]\n---\n# Head\n\n$x$ ![i](i.png \"Cap\")")).unwrap();
+        a.assets = Some(String::from("2026/a/"));
+        let b = Article::new(String::from("---\ntitle: B\ndate: 2026-01-01\ntoc: false\n---\nText")).unwrap();
+        let articles = [(a, String::from("2026/a.html")), (b, String::from("b.html"))];
+        let sorted = articles.iter().collect::<Vec<_>>();
+
+        for author in [None, Some(String::from("Site author"))] {
+            let site = Site { name: String::from("Blog"), base_url: Some(String::from("https://x.org")), language: String::from("en"), author };
+            for (article, _) in &articles {
+                let options = Options::default().with(&article.markdown);
+                let document = Document::new(article.body().unwrap(), options.extensions, "a/");
+                check(&env, "article.html", crate::ArticleTemplate {
+                    site:    &site,
+                    title:   article.name(),
+                    meta:    article,
+                    head:    "<style></style>",
+                    header:  "<nav></nav>",
+                    footer:  "",
+                    root:    "../",
+                    ts:      article.ts,
+                    toc:     article.toc.then(|| document.toc()),
+                    content: Markdown { document: std::borrow::Cow::Borrowed(&document), options, images: None },
+                });
+            }
+            for (page, pages) in [(1, 1), (1, 2), (2, 3)] {
+                check(&env, "index.html", crate::IndexTemplate {
+                    site:     &site,
+                    head:     "",
+                    header:   "",
+                    footer:   "",
+                    root:     "./",
+                    articles: &sorted,
+                    intro:    (page == 1).then(|| Markdown::new("*Hi*", Options::default(), "")),
+                    page,
+                    pages,
+                    prev:     (page > 1).then(|| String::from("../index.html")),
+                    next:     (page < pages).then(|| String::from("page/2.html")),
+                });
+            }
+            let terms = taxonomy::collect(&sorted, |a| &a.tags);
+            check(&env, "taxonomy.html", crate::TaxonomyTemplate { site: &site, title: "Tags", head: "", header: "", footer: "", terms: &terms });
+            check(&env, "term.html", crate::TermTemplate { site: &site, title: "Tags", head: "", header: "", footer: "", root: "../", term: &terms[0] });
+            check(&env, "search.html", crate::SearchTemplate { site: &site, head: "", header: "", footer: "" });
+
+            let options = Options { classes: crate::tree_sitter_html::Classes::Inline, ..Options::default() };
+            let updated = articles[0].0.modified();
+            check(&env, "atom.xml", crate::AtomTemplate { site: &site, base_url: "https://x.org", options, updated, articles: &sorted });
+            check(&env, "rss.xml", crate::RssTemplate { site: &site, base_url: "https://x.org", options, updated, articles: &sorted });
+            check(&env, "sitemap.xml", crate::SitemapTemplate { base_url: "https://x.org", updated, articles: &sorted });
+        }
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = crate::test_utils::TempDir::new("templates");
+        std::fs::write(dir.join("index.html"), "<p>\n{% if %}</p>").unwrap();
+        let e = load(&dir).unwrap_err();
+        assert!(e.starts_with(&format!("{}:2 - ", dir.join("index.html").display())), "{e}");
+    }
+}
@@ -0,0 +1,30 @@
+// INFO Helpers shared by tests of several modules
+
+use std::path::{ Path, PathBuf };
+
+/// Empty directory in the system temp directory, removed with its contents on drop
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// "blog_generator_<name>_<pid>", so parallel tests and test runs don't share it
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("blog_generator_{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl std::ops::Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::tree_sitter_html::Classes;
+
+/// Light and dark colors per capture name, dotted captures without an entry
+/// inherit the color of their first part, e.g. "keyword.conditional" from "keyword"
+const PALETTE: &[(&str, &str, &str, &str)] = &[
+    // capture                 light      dark       extra
+    ("comment",                "#6a737d", "#8b949e", "font-style: italic;"),
+    ("comment.documentation",  "#57606a", "#9ea7b3", "font-style: italic;"),
+    ("tag",                    "#d73a49", "#ff7b72", ""),
+    ("keyword",                "#d73a49", "#ff7b72", ""),
+    ("keyword.return",         "#cf222e", "#ffa198", "font-weight: bold;"),
+    ("function",               "#6f42c1", "#d2a8ff", ""),
+    ("function.builtin",       "#8250df", "#e2c5ff", ""),
+    ("string",                 "#032f62", "#a5d6ff", ""),
+    ("string.special",         "#116329", "#7ee787", ""),
+    ("type",                   "#953800", "#ffa657", ""),
+    ("number",                 "#005cc5", "#79c0ff", ""),
+    ("property",               "#005cc5", "#79c0ff", ""),
+    ("variable",               "#24292e", "#c9d1d9", ""),
+    ("variable.builtin",       "#005cc5", "#79c0ff", ""),
+    ("label",                  "#6f42c1", "#d2a8ff", ""),
+    ("constant",               "#005cc5", "#79c0ff", ""),
+    ("operator",               "#d73a49", "#ff7b72", ""),
+    ("attribute",              "#22863a", "#7ee787", ""),
+    ("module",                 "#953800", "#ffa657", ""),
+];
+
+/// Code block colors: background, text, highlighted line, line numbers
+const BLOCK: [(&str, &str); 4] = [
+    ("#f6f8fa", "#161b22"),
+    ("#24292e", "#c9d1d9"),
+    ("#fff8c5", "#3b2e00"),
+    ("#8c959f", "#6e7681"),
+];
+
+fn pick((light, dark): (&'static str, &'static str), is_dark: bool) -> &'static str {
+    if is_dark { dark } else { light }
+}
+
+fn entry(capture: &str) -> Option<&'static (&'static str, &'static str, &'static str, &'static str)> {
+    PALETTE.iter()
+        .find(|v| v.0 == capture)
+        .or_else(|| PALETTE.iter().find(|v| capture.split('.').next() == Some(v.0)))
+}
+
+/// Inline style of a capture in the light theme
+pub fn style(capture: &str) -> String {
+    match entry(capture) {
+        Some((_, light, _, "")) => format!("color: {light};"),
+        Some((_, light, _, extra)) => format!("color: {light}; {extra}"),
+        None => String::new(),
+    }
+}
+
+/// Inline style of a line wrapper in the light theme
+pub fn line_style(highlight: bool) -> String {
+    match highlight {
+        true => format!("display: inline-block; min-width: 100%; background: {};", BLOCK[2].0),
+        false => String::from("display: inline-block; min-width: 100%;"),
+    }
+}
+
+/// Inline style of a line number in the light theme
+pub fn line_number_style() -> String {
+    format!("display: inline-block; min-width: 3ch; margin-right: 1em; text-align: right; color: {}; user-select: none;", BLOCK[3].0)
+}
+
+/// Inline style of a code block in the light theme
+pub fn block_style() -> String {
+    format!("background: {}; color: {};", BLOCK[0].0, BLOCK[1].0)
+}
+
+/// Stylesheet for the spans and line wrappers produced by `LangDb::html`
+pub fn css(classes: Classes, dark: bool) -> String {
+    let [bg, fg, hl, num] = BLOCK.map(|v| pick(v, dark));
+
+    let mut s = format!(
+        "/* Generated by {} v{} */\n\
+        pre code {{ display: block; overflow-x: auto; background: {bg}; color: {fg}; }}\n\
+        pre code .line {{ display: inline-block; min-width: 100%; }}\n\
+        pre code .line.highlight {{ background: {hl}; }}\n\
+        pre code .line-number {{ display: inline-block; min-width: 3ch; margin-right: 1em; text-align: right; color: {num}; user-select: none; }}\n",
+        env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"),
+    );
+
+    let mut done = Vec::new();
+    for (capture, class) in classes.table() {
+        let Some((_, light, dark_color, extra)) = entry(capture) else {
+            continue;
+        };
+        if done.contains(&class) {
+            continue;
+        }
+        done.push(class);
+
+        // INFO Dots of full capture names are part of the class, not class separators
+        let selector = format!(".{}", class.replace('.', "\\."));
+        let extra = match *extra {
+            "" => String::new(),
+            extra => format!(" {extra}"),
+        };
+        s.push_str(&format!("pre code {selector} {{ color: {};{extra} }}\n", pick((light, dark_color), dark)));
+    }
+
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_css() {
+        let short = css(Classes::Short, false);
+        assert!(short.contains("pre code .kw { color: #d73a49; }"), "{short}");
+        assert!(short.contains("pre code .comm { color: #6a737d; font-style: italic; }"), "{short}");
+        assert_eq!(short.matches("pre code .kw ").count(), 1);
+
+        let full = css(Classes::Full, true);
+        assert!(full.contains("pre code .keyword { color: #ff7b72; }"), "{full}");
+        assert!(full.contains("pre code .keyword\\.return { color: #ffa198; font-weight: bold; }"), "{full}");
+        assert!(full.contains("pre code .keyword\\.conditional { color: #ff7b72; }"), "{full}");
+
+        assert_eq!(style("comment.documentation"), "color: #57606a; font-style: italic;");
+        assert_eq!(style("keyword.conditional"), "color: #d73a49;");
+        assert_eq!(style("unknown"), "");
+
+        for (_, class) in Classes::Short.table() {
+            assert!(short.contains(&format!(".{class} ")), "Missing {class}");
+        }
+        for (_, class) in Classes::Full.table() {
+            assert!(full.contains(&format!(".{} ", class.replace('.', "\\."))), "Missing {class}");
+        }
+        for (capture, _) in Classes::Inline.table() {
+            assert!(!style(capture).is_empty(), "Missing {capture}");
+        }
+    }
+}
@@ -1,33 +1,34 @@
+use std::path::{ Path, PathBuf };
 use tree_sitter_highlight::{ HighlightConfiguration, Highlighter, HighlightEvent };
 
-use tree_sitter_asm        as asm;
-use tree_sitter_bash       as bash;
-use tree_sitter_c          as c;
-use tree_sitter_cpp        as cpp;
-use tree_sitter_css        as css;
-use tree_sitter_c_sharp    as c_sharp;
-use tree_sitter_elixir     as elixir;
-use tree_sitter_fsharp     as fsharp;
-use tree_sitter_java       as java;
-use tree_sitter_javascript as js;
-use tree_sitter_julia      as julia;
-use tree_sitter_html       as html;
-use tree_sitter_kotlin_sg  as kotlin;
-use tree_sitter_lua        as lua;
-use tree_sitter_go         as go;
-use tree_sitter_ocaml      as ocaml;
-use tree_sitter_pascal     as pascal;
-use tree_sitter_php        as php;
-use tree_sitter_powershell as pwsh;
-use tree_sitter_python     as python;
-use tree_sitter_ruby       as ruby;
-use tree_sitter_rust       as rust;
-use tree_sitter_scala      as scala;
-use tree_sitter_sequel     as sql;
-use tree_sitter_swift      as swift;
-use tree_sitter_typescript as ts;
-use tree_sitter_xml        as xml;
-use tree_sitter_zig        as zig;
+#[cfg(feature = "lang-asm")]                          use tree_sitter_asm        as asm;
+#[cfg(feature = "lang-bash")]                         use tree_sitter_bash       as bash;
+#[cfg(any(feature = "lang-c", feature = "lang-cpp"))] use tree_sitter_c          as c;
+#[cfg(feature = "lang-cpp")]                          use tree_sitter_cpp        as cpp;
+#[cfg(feature = "lang-css")]                          use tree_sitter_css        as css;
+#[cfg(feature = "lang-csharp")]                       use tree_sitter_c_sharp    as c_sharp;
+#[cfg(feature = "lang-elixir")]                       use tree_sitter_elixir     as elixir;
+#[cfg(feature = "lang-fsharp")]                       use tree_sitter_fsharp     as fsharp;
+#[cfg(feature = "lang-java")]                         use tree_sitter_java       as java;
+#[cfg(any(feature = "lang-js", feature = "lang-ts"))] use tree_sitter_javascript as js;
+#[cfg(feature = "lang-julia")]                        use tree_sitter_julia      as julia;
+#[cfg(feature = "lang-html")]                         use tree_sitter_html       as html;
+#[cfg(feature = "lang-kotlin")]                       use tree_sitter_kotlin_sg  as kotlin;
+#[cfg(feature = "lang-lua")]                          use tree_sitter_lua        as lua;
+#[cfg(feature = "lang-go")]                           use tree_sitter_go         as go;
+#[cfg(feature = "lang-ocaml")]                        use tree_sitter_ocaml      as ocaml;
+#[cfg(feature = "lang-pascal")]                       use tree_sitter_pascal     as pascal;
+#[cfg(feature = "lang-php")]                          use tree_sitter_php        as php;
+#[cfg(feature = "lang-pwsh")]                         use tree_sitter_powershell as pwsh;
+#[cfg(feature = "lang-python")]                       use tree_sitter_python     as python;
+#[cfg(feature = "lang-ruby")]                         use tree_sitter_ruby       as ruby;
+#[cfg(feature = "lang-rust")]                         use tree_sitter_rust       as rust;
+#[cfg(feature = "lang-scala")]                        use tree_sitter_scala      as scala;
+#[cfg(feature = "lang-sql")]                          use tree_sitter_sequel     as sql;
+#[cfg(feature = "lang-swift")]                        use tree_sitter_swift      as swift;
+#[cfg(feature = "lang-ts")]                           use tree_sitter_typescript as ts;
+#[cfg(feature = "lang-xml")]                          use tree_sitter_xml        as xml;
+#[cfg(feature = "lang-zig")]                          use tree_sitter_zig        as zig;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Lang {
@@ -62,7 +63,6 @@ pub enum Lang {
 }
 
 struct Dataset<'a> {
-    name:       &'a str,
     f:          fn() -> tree_sitter::Language,
     highlights: &'a str,
     injection:  &'a str,
@@ -73,7 +73,7 @@ impl Lang {
     pub fn form_str(s: &str) -> Option<Self> {
         let s = s.trim();
 
-        if s.len() == 0 || s.len() > 16 || !s.is_ascii() {
+        if s.is_empty() || s.len() > 16 || !s.is_ascii() {
             return None;
         }
 
@@ -110,47 +110,124 @@ impl Lang {
             b"ts" | b"typescript"                    => Some(Lang::Ts),
             b"xml" | b"rss" | b"svg"                 => Some(Lang::Xml),
             b"zig"                                   => Some(Lang::Zig),
-            b"text" | _                              => None,
+            _                                        => None,
         }
     }
 
-    fn dataset(self) -> Dataset<'static> {
+    /// Canonical name, also used in "lang-<name>" cargo features
+    pub fn name(self) -> &'static str {
         match self {
-            Lang::Asm    => Dataset { name:"asm",    f: || asm::LANGUAGE.into(),           highlights: asm::HIGHLIGHTS_QUERY,                     injection: "",                       locals: "" },
-            Lang::Bash   => Dataset { name:"bash",   f: || bash::LANGUAGE.into(),          highlights: bash::HIGHLIGHT_QUERY,                     injection: "",                       locals: "" },
-            Lang::C      => Dataset { name:"c",      f: || c::LANGUAGE.into(),             highlights: c::HIGHLIGHT_QUERY,                        injection: "",                       locals: "" },
-            Lang::Cpp    => Dataset { name:"cpp",    f: || cpp::LANGUAGE.into(),           highlights: CPP_HIGHLIGHTNING,                         injection: "",                       locals: "" },
-            Lang::Css    => Dataset { name:"css",    f: || css::LANGUAGE.into(),           highlights: css::HIGHLIGHTS_QUERY,                     injection: "",                       locals: "" },
-            Lang::Csharp => Dataset { name:"csharp", f: || c_sharp::LANGUAGE.into(),       highlights: include_str!("../highlights/c_sharp.scm"), injection: "",                       locals: "" },
-            Lang::Elixir => Dataset { name:"elixir", f: || elixir::LANGUAGE.into(),        highlights: elixir::HIGHLIGHTS_QUERY,                  injection: elixir::INJECTIONS_QUERY, locals: "" },
-            Lang::Fsharp => Dataset { name:"fsharp", f: || fsharp::LANGUAGE_FSHARP.into(), highlights: fsharp::HIGHLIGHTS_QUERY,                  injection: fsharp::INJECTIONS_QUERY, locals: fsharp::LOCALS_QUERY },
-            Lang::Java   => Dataset { name:"java",   f: || java::LANGUAGE.into(),          highlights: java::HIGHLIGHTS_QUERY,                    injection: "",                       locals: "" },
-            Lang::Js     => Dataset { name:"js",     f: || js::LANGUAGE.into(),            highlights: js::HIGHLIGHT_QUERY,                       injection: js::INJECTIONS_QUERY,     locals: js::LOCALS_QUERY },
-            Lang::Julia  => Dataset { name:"julia",  f: || julia::LANGUAGE.into(),         highlights: include_str!("../highlights/julia.scm"),   injection: "",                       locals: "" },
-            Lang::Html   => Dataset { name:"html",   f: || html::LANGUAGE.into(),          highlights: html::HIGHLIGHTS_QUERY,                    injection: html::INJECTIONS_QUERY,   locals: "" },
-            Lang::Kotlin => Dataset { name:"kotlin", f: || kotlin::LANGUAGE.into(),        highlights: kotlin::HIGHLIGHTS_QUERY,                  injection: "",                       locals: "" },
-            Lang::Lua    => Dataset { name:"lua",    f: || lua::LANGUAGE.into(),           highlights: lua::HIGHLIGHTS_QUERY,                     injection: lua::INJECTIONS_QUERY,    locals: lua::LOCALS_QUERY },
-            Lang::Go     => Dataset { name:"go",     f: || go::LANGUAGE.into(),            highlights: go::HIGHLIGHTS_QUERY,                      injection: "",                       locals: "" },
-            Lang::Ocaml  => Dataset { name:"ocaml",  f: || ocaml::LANGUAGE_OCAML.into(),   highlights: ocaml::HIGHLIGHTS_QUERY,                   injection: "",                       locals: ocaml::LOCALS_QUERY },
-            Lang::Pascal => Dataset { name:"pascal", f: || pascal::LANGUAGE.into(),        highlights: include_str!("../highlights/pascal.scm"),  injection: "",                       locals: "" },
-            Lang::Php    => Dataset { name:"php",    f: || php::LANGUAGE_PHP_ONLY.into(),  highlights: php::HIGHLIGHTS_QUERY,                     injection: php::INJECTIONS_QUERY,    locals: "" },
-            Lang::Pwsh   => Dataset { name:"pwsh",   f: || pwsh::LANGUAGE.into(),          highlights: include_str!("../highlights/pwsh.scm"),    injection: "",                       locals: "" },
-            Lang::Python => Dataset { name:"python", f: || python::LANGUAGE.into(),        highlights: python::HIGHLIGHTS_QUERY,                  injection: "",                       locals: "" },
-            Lang::Ruby   => Dataset { name:"ruby",   f: || ruby::LANGUAGE.into(),          highlights: ruby::HIGHLIGHTS_QUERY,                    injection: "",                       locals: "" },
-            Lang::Rust   => Dataset { name:"rust",   f: || rust::LANGUAGE.into(),          highlights: rust::HIGHLIGHTS_QUERY,                    injection: rust::INJECTIONS_QUERY,   locals: "" },
-            Lang::Scala  => Dataset { name:"scala",  f: || scala::LANGUAGE.into(),         highlights: scala::HIGHLIGHTS_QUERY,                   injection: "",                       locals: scala::LOCALS_QUERY },
-            Lang::Sql    => Dataset { name:"sql",    f: || sql::LANGUAGE.into(),           highlights: sql::HIGHLIGHTS_QUERY,                     injection: "",                       locals: "" },
-            Lang::Swift  => Dataset { name:"swift",  f: || swift::LANGUAGE.into(),         highlights: swift::HIGHLIGHTS_QUERY,                   injection: swift::INJECTIONS_QUERY,  locals: swift::LOCALS_QUERY },
-            Lang::Ts     => Dataset { name:"ts",     f: || ts::LANGUAGE_TYPESCRIPT.into(), highlights: TS_HIGHLIGHTNING,                          injection: "",                       locals: ts::LOCALS_QUERY },
-            Lang::Xml    => Dataset { name:"xml",    f: || xml::LANGUAGE_XML.into(),       highlights: xml::XML_HIGHLIGHT_QUERY,                  injection: "",                       locals: "" },
-            Lang::Zig    => Dataset { name:"zig",    f: || zig::LANGUAGE.into(),           highlights: zig::HIGHLIGHTS_QUERY,                     injection: zig::INJECTIONS_QUERY,    locals: "" },
+            Lang::Asm    => "asm",
+            Lang::Bash   => "bash",
+            Lang::C      => "c",
+            Lang::Cpp    => "cpp",
+            Lang::Css    => "css",
+            Lang::Csharp => "csharp",
+            Lang::Elixir => "elixir",
+            Lang::Fsharp => "fsharp",
+            Lang::Java   => "java",
+            Lang::Js     => "js",
+            Lang::Julia  => "julia",
+            Lang::Html   => "html",
+            Lang::Kotlin => "kotlin",
+            Lang::Lua    => "lua",
+            Lang::Go     => "go",
+            Lang::Ocaml  => "ocaml",
+            Lang::Pascal => "pascal",
+            Lang::Php    => "php",
+            Lang::Pwsh   => "pwsh",
+            Lang::Python => "python",
+            Lang::Ruby   => "ruby",
+            Lang::Rust   => "rust",
+            Lang::Scala  => "scala",
+            Lang::Sql    => "sql",
+            Lang::Swift  => "swift",
+            Lang::Ts     => "ts",
+            Lang::Xml    => "xml",
+            Lang::Zig    => "zig",
+        }
+    }
+
+    /// Grammar and queries, `None` when the language is disabled by cargo features
+    fn dataset(self) -> Option<Dataset<'static>> {
+        match self {
+            #[cfg(feature = "lang-asm")]
+            Lang::Asm    => Some(Dataset { f: || asm::LANGUAGE.into(),           highlights: asm::HIGHLIGHTS_QUERY,                     injection: "",                       locals: "" }),
+            #[cfg(feature = "lang-bash")]
+            Lang::Bash   => Some(Dataset { f: || bash::LANGUAGE.into(),          highlights: bash::HIGHLIGHT_QUERY,                     injection: "",                       locals: "" }),
+            #[cfg(feature = "lang-c")]
+            Lang::C      => Some(Dataset { f: || c::LANGUAGE.into(),             highlights: c::HIGHLIGHT_QUERY,                        injection: "",                       locals: "" }),
+            #[cfg(feature = "lang-cpp")]
+            Lang::Cpp    => Some(Dataset { f: || cpp::LANGUAGE.into(),           highlights: CPP_HIGHLIGHTNING,                         injection: "",                       locals: "" }),
+            #[cfg(feature = "lang-css")]
+            Lang::Css    => Some(Dataset { f: || css::LANGUAGE.into(),           highlights: css::HIGHLIGHTS_QUERY,                     injection: "",                       locals: "" }),
+            #[cfg(feature = "lang-csharp")]
+            Lang::Csharp => Some(Dataset { f: || c_sharp::LANGUAGE.into(),       highlights: include_str!("../highlights/c_sharp.scm"), injection: "",                       locals: "" }),
+            #[cfg(feature = "lang-elixir")]
+            Lang::Elixir => Some(Dataset { f: || elixir::LANGUAGE.into(),        highlights: elixir::HIGHLIGHTS_QUERY,                  injection: elixir::INJECTIONS_QUERY, locals: "" }),
+            #[cfg(feature = "lang-fsharp")]
+            Lang::Fsharp => Some(Dataset { f: || fsharp::LANGUAGE_FSHARP.into(), highlights: fsharp::HIGHLIGHTS_QUERY,                  injection: fsharp::INJECTIONS_QUERY, locals: fsharp::LOCALS_QUERY }),
+            #[cfg(feature = "lang-java")]
+            Lang::Java   => Some(Dataset { f: || java::LANGUAGE.into(),          highlights: java::HIGHLIGHTS_QUERY,                    injection: "",                       locals: "" }),
+            #[cfg(feature = "lang-js")]
+            Lang::Js     => Some(Dataset { f: || js::LANGUAGE.into(),            highlights: js::HIGHLIGHT_QUERY,                       injection: js::INJECTIONS_QUERY,     locals: js::LOCALS_QUERY }),
+            #[cfg(feature = "lang-julia")]
+            Lang::Julia  => Some(Dataset { f: || julia::LANGUAGE.into(),         highlights: include_str!("../highlights/julia.scm"),   injection: "",                       locals: "" }),
+            #[cfg(feature = "lang-html")]
+            Lang::Html   => Some(Dataset { f: || html::LANGUAGE.into(),          highlights: html::HIGHLIGHTS_QUERY,                    injection: html::INJECTIONS_QUERY,   locals: "" }),
+            #[cfg(feature = "lang-kotlin")]
+            Lang::Kotlin => Some(Dataset { f: || kotlin::LANGUAGE.into(),        highlights: kotlin::HIGHLIGHTS_QUERY,                  injection: "",                       locals: "" }),
+            #[cfg(feature = "lang-lua")]
+            Lang::Lua    => Some(Dataset { f: || lua::LANGUAGE.into(),           highlights: lua::HIGHLIGHTS_QUERY,                     injection: lua::INJECTIONS_QUERY,    locals: lua::LOCALS_QUERY }),
+            #[cfg(feature = "lang-go")]
+            Lang::Go     => Some(Dataset { f: || go::LANGUAGE.into(),            highlights: go::HIGHLIGHTS_QUERY,                      injection: "",                       locals: "" }),
+            #[cfg(feature = "lang-ocaml")]
+            Lang::Ocaml  => Some(Dataset { f: || ocaml::LANGUAGE_OCAML.into(),   highlights: ocaml::HIGHLIGHTS_QUERY,                   injection: "",                       locals: ocaml::LOCALS_QUERY }),
+            #[cfg(feature = "lang-pascal")]
+            Lang::Pascal => Some(Dataset { f: || pascal::LANGUAGE.into(),        highlights: include_str!("../highlights/pascal.scm"),  injection: "",                       locals: "" }),
+            #[cfg(feature = "lang-php")]
+            Lang::Php    => Some(Dataset { f: || php::LANGUAGE_PHP_ONLY.into(),  highlights: php::HIGHLIGHTS_QUERY,                     injection: php::INJECTIONS_QUERY,    locals: "" }),
+            #[cfg(feature = "lang-pwsh")]
+            Lang::Pwsh   => Some(Dataset { f: || pwsh::LANGUAGE.into(),          highlights: include_str!("../highlights/pwsh.scm"),    injection: "",                       locals: "" }),
+            #[cfg(feature = "lang-python")]
+            Lang::Python => Some(Dataset { f: || python::LANGUAGE.into(),        highlights: python::HIGHLIGHTS_QUERY,                  injection: "",                       locals: "" }),
+            #[cfg(feature = "lang-ruby")]
+            Lang::Ruby   => Some(Dataset { f: || ruby::LANGUAGE.into(),          highlights: ruby::HIGHLIGHTS_QUERY,                    injection: "",                       locals: "" }),
+            #[cfg(feature = "lang-rust")]
+            Lang::Rust   => Some(Dataset { f: || rust::LANGUAGE.into(),          highlights: rust::HIGHLIGHTS_QUERY,                    injection: include_str!("../highlights/rust_injections.scm"), locals: "" }),
+            #[cfg(feature = "lang-scala")]
+            Lang::Scala  => Some(Dataset { f: || scala::LANGUAGE.into(),         highlights: scala::HIGHLIGHTS_QUERY,                   injection: "",                       locals: scala::LOCALS_QUERY }),
+            #[cfg(feature = "lang-sql")]
+            Lang::Sql    => Some(Dataset { f: || sql::LANGUAGE.into(),           highlights: sql::HIGHLIGHTS_QUERY,                     injection: "",                       locals: "" }),
+            #[cfg(feature = "lang-swift")]
+            Lang::Swift  => Some(Dataset { f: || swift::LANGUAGE.into(),         highlights: swift::HIGHLIGHTS_QUERY,                   injection: swift::INJECTIONS_QUERY,  locals: swift::LOCALS_QUERY }),
+            #[cfg(feature = "lang-ts")]
+            Lang::Ts     => Some(Dataset { f: || ts::LANGUAGE_TYPESCRIPT.into(), highlights: TS_HIGHLIGHTNING,                          injection: "",                       locals: ts::LOCALS_QUERY }),
+            #[cfg(feature = "lang-xml")]
+            Lang::Xml    => Some(Dataset { f: || xml::LANGUAGE_XML.into(),       highlights: xml::XML_HIGHLIGHT_QUERY,                  injection: "",                       locals: "" }),
+            #[cfg(feature = "lang-zig")]
+            Lang::Zig    => Some(Dataset { f: || zig::LANGUAGE.into(),           highlights: zig::HIGHLIGHTS_QUERY,                     injection: zig::INJECTIONS_QUERY,    locals: "" }),
+            #[allow(unreachable_patterns)]
+            _ => None,
         }
     }
 }
 
-pub struct LangDb([std::sync::OnceLock<HighlightConfiguration>; 28]);
+const LANGS: usize = Lang::Zig as usize + 1;
 
+/// Bundled configurations, initialized on first use, `None` for languages disabled by cargo features
+pub struct LangDb([std::sync::OnceLock<Option<HighlightConfiguration>>; LANGS], std::sync::OnceLock<Runtime>);
+
+/// Languages loaded from "<dir>/<name>/" by `LangDb::load`
+struct Runtime {
+    grammars:   Vec<(String, HighlightConfiguration)>,
+    overrides:  Vec<(Lang, HighlightConfiguration)>,
+    _libraries: Vec<libloading::Library>,
+}
+
+#[cfg(feature = "lang-cpp")]
 const CPP_HIGHLIGHTNING: &str = const_format::concatcp!(c::HIGHLIGHT_QUERY, "\n", cpp::HIGHLIGHT_QUERY);
+#[cfg(feature = "lang-ts")]
 const TS_HIGHLIGHTNING: &str = const_format::concatcp!(js::HIGHLIGHT_QUERY, "\n", ts::HIGHLIGHTS_QUERY);
 
 const ENTITIES: &[&str] = &[
@@ -219,48 +296,310 @@ const CSS: &[&str] = &[
 
 const _: () = assert!(ENTITIES.len() == CSS.len());
 
+/// Class names of highlighted spans, `Short` groups captures into a few classes like "kw",
+/// `Full` keeps dotted capture names like "keyword.return", selected in CSS as `.keyword\.return`,
+/// `Inline` writes colors of the light theme as style attributes for outputs without stylesheets
+#[derive(Debug, Default, Clone, Copy, PartialEq, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Classes {
+    #[default]
+    Short,
+    Full,
+    Inline,
+}
+
+impl Classes {
+    fn names(self) -> &'static [&'static str] {
+        match self {
+            Classes::Short | Classes::Inline => CSS,
+            Classes::Full                    => ENTITIES,
+        }
+    }
+
+    /// Capture names paired with their class attributes
+    pub fn table(self) -> impl Iterator<Item = (&'static str, &'static str)> {
+        ENTITIES.iter().copied().zip(self.names().iter().copied())
+    }
+}
+
 impl LangDb {
     pub const fn new() -> Self {
-        unsafe {
-            let mut a: [std::mem::MaybeUninit<std::sync::OnceLock<HighlightConfiguration>>; 28] = std::mem::MaybeUninit::uninit().assume_init();
-            
-            let mut i = 0;
-            while i < 28 {
-                a[i] = std::mem::MaybeUninit::new(std::sync::OnceLock::new());
-                i += 1;
+        Self([const { std::sync::OnceLock::new() }; LANGS], std::sync::OnceLock::new())
+    }
+
+    /// Loads languages from `dir`, "<lang>.scm" files replace highlights queries of bundled languages,
+    /// "<lang>/" subdirectories have "highlights.scm", optional "injections.scm" and "locals.scm",
+    /// and a compiled grammar ("*.so", "*.dylib" or "*.dll") exporting `tree_sitter_<lang>`.
+    /// Without a grammar the name must be a bundled language and the queries replace the bundled ones.
+    /// Returns the number of loaded languages
+    pub fn load(&self, dir: &Path) -> Result<usize, String> {
+        let mut runtime = Runtime { grammars: Vec::new(), overrides: Vec::new(), _libraries: Vec::new() };
+
+        // INFO Languages by name with their "<lang>.scm" file and "<lang>/" directory
+        let mut langs = std::collections::BTreeMap::<String, (Option<PathBuf>, Option<PathBuf>)>::new();
+        for path in sorted_dir(dir)? {
+            let is_query = path.extension().is_some_and(|v| v == "scm") && path.is_file();
+            if !is_query && !path.is_dir() {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|v| v.to_str()).map(str::to_ascii_lowercase) else {
+                return Err(format!("{path:?} - invalid name"));
+            };
+            let lang = langs.entry(name).or_default();
+            match is_query {
+                true => lang.0 = Some(path),
+                false => lang.1 = Some(path),
             }
-            
-            Self(std::mem::transmute(a))
         }
+
+        for (name, (file, path)) in langs {
+            let read = |path: PathBuf| match std::fs::read_to_string(&path) {
+                Ok(s) => Ok(Some(s)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(format!("{path:?} - {e}")),
+            };
+            let query = |file: &str| path.as_ref().map_or(Ok(None), |v| read(v.join(file)));
+            let (mut highlights, injections, locals) = (query("highlights.scm")?, query("injections.scm")?, query("locals.scm")?);
+            if let Some(file) = file {
+                if highlights.is_some() {
+                    return Err(format!("{file:?} - highlights are also in {:?}", path.unwrap_or_default().join("highlights.scm")));
+                }
+                highlights = read(file)?;
+            }
+            let path = path.unwrap_or_else(|| dir.join(&name));
+
+            let mut grammars = match path.is_dir() {
+                true => sorted_dir(&path)?,
+                false => Vec::new(),
+            };
+            grammars.retain(|v| matches!(v.extension().and_then(|v| v.to_str()), Some("so" | "dylib" | "dll")));
+            if grammars.len() > 1 {
+                return Err(format!("{path:?} - more than one compiled grammar {grammars:?}"));
+            }
+
+            match grammars.pop() {
+                Some(grammar) => {
+                    let highlights = highlights.ok_or_else(|| format!("{:?} - missing", path.join("highlights.scm")))?;
+                    let (library, language) = load_grammar(&grammar, &name)?;
+                    let cfg = configure(language, &name, &highlights, injections.as_deref().unwrap_or_default(), locals.as_deref().unwrap_or_default())
+                        .map_err(|e| format!("{path:?} - {e}"))?;
+                    runtime._libraries.push(library);
+                    runtime.grammars.push((name, cfg));
+                },
+                None => {
+                    let lang = Lang::form_str(&name).ok_or_else(|| format!("{path:?} - unknown language without a compiled grammar"))?;
+                    let v = lang.dataset().ok_or_else(|| format!("{path:?} - \"{}\" is disabled, enable the \"lang-{0}\" feature or add a compiled grammar", lang.name()))?;
+                    let cfg = configure((v.f)(), lang.name(), highlights.as_deref().unwrap_or(v.highlights), injections.as_deref().unwrap_or(v.injection), locals.as_deref().unwrap_or(v.locals))
+                        .map_err(|e| format!("{path:?} - {e}"))?;
+                    runtime.overrides.push((lang, cfg));
+                },
+            }
+        }
+
+        let count = runtime.grammars.len() + runtime.overrides.len();
+        self.1.set(runtime).map_err(|_| String::from("languages are already loaded"))?;
+        Ok(count)
     }
 
-    pub fn html<W: pulldown_cmark_escape::StrWrite>(&self, s: &str, lang: Lang, mut w: W) -> Result<(), W::Error> {
-        let cfg = self.0[lang as usize].get_or_init(|| {
-            let v = lang.dataset();
-            let mut cfg = HighlightConfiguration::new((v.f)(), v.name, v.highlights, v.injection, v.locals)
-                .unwrap_or_else(|e| panic!("Can't load {lang:?}: {e:?}"));
-            cfg.configure(ENTITIES);
-            cfg
-        });
+    pub fn html<W: pulldown_cmark_escape::StrWrite>(&self, s: &str, lang: Option<&str>, lines: &Lines, classes: Classes, w: W) -> Result<(), W::Error> {
+        let mut w = LineWriter::new(w, lines, classes);
+
+        let Some(cfg) = lang.and_then(|v| self.resolve(v)) else {
+            w.text(s)?;
+            return w.finish();
+        };
 
         let mut highlighter = Highlighter::new();
 
-        for v in highlighter.highlight(&cfg, s.as_bytes(), None, |_| None).unwrap() {
+        for v in highlighter.highlight(cfg, s.as_bytes(), None, |name| self.resolve(name)).unwrap() {
             match v.unwrap() {
-                HighlightEvent::Source { start, end } => {
-                    pulldown_cmark_escape::escape_html(&mut w, std::str::from_utf8(&s.as_bytes()[start..end]).unwrap())?;
-                }
-                HighlightEvent::HighlightStart(h) => {
-                    if let Some(name) = CSS.get(h.0) {
-                        write!(w, "<span class=\"{name}\">")?;
-                    }
-                }
-                HighlightEvent::HighlightEnd => {
-                    write!(w, "</span>")?;
-                }
+                HighlightEvent::Source { start, end } => w.text(&s[start..end])?,
+                HighlightEvent::HighlightStart(h) => w.push(h.0)?,
+                HighlightEvent::HighlightEnd => w.pop()?,
             }
         }
 
+        w.finish()
+    }
+
+    /// Runtime grammars by directory name first, then bundled languages by alias
+    fn resolve(&self, name: &str) -> Option<&HighlightConfiguration> {
+        let name = name.trim().to_ascii_lowercase();
+        if let Some(runtime) = self.1.get() && let Some((_, cfg)) = runtime.grammars.iter().find(|v| v.0 == name) {
+            return Some(cfg);
+        }
+        Lang::form_str(&name).and_then(|lang| self.config(lang))
+    }
+
+    fn config(&self, lang: Lang) -> Option<&HighlightConfiguration> {
+        if let Some(runtime) = self.1.get() && let Some((_, cfg)) = runtime.overrides.iter().find(|v| v.0 == lang) {
+            return Some(cfg);
+        }
+        self.0[lang as usize].get_or_init(|| {
+            let Some(v) = lang.dataset() else {
+                println!("Warning: \"{}\" highlighting is disabled in this build, enable the \"lang-{0}\" feature, plain code is used", lang.name());
+                return None;
+            };
+            let cfg = configure((v.f)(), lang.name(), v.highlights, v.injection, v.locals)
+                .unwrap_or_else(|e| panic!("Can't load {lang:?}: {e}"));
+            Some(cfg)
+        }).as_ref()
+    }
+}
+
+fn configure(language: tree_sitter::Language, name: &str, highlights: &str, injections: &str, locals: &str) -> Result<HighlightConfiguration, String> {
+    let mut cfg = HighlightConfiguration::new(language, name, highlights, injections, locals)
+        .map_err(|e| format!("invalid query - {e}"))?;
+    cfg.configure(ENTITIES);
+    Ok(cfg)
+}
+
+/// Paths of entries of `dir` in order
+fn sorted_dir(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut result = std::fs::read_dir(dir).map_err(|e| format!("{dir:?} - {e}"))?
+        .map(|v| v.map(|v| v.path()).map_err(|e| format!("{dir:?} - {e}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    result.sort_unstable();
+    Ok(result)
+}
+
+fn load_grammar(path: &Path, name: &str) -> Result<(libloading::Library, tree_sitter::Language), String> {
+    let symbol = format!("tree_sitter_{}", name.replace('-', "_"));
+
+    // SAFETY: the library is a tree-sitter grammar provided by the user, it's kept loaded
+    // as long as the `LangDb` since the language refers to its static tables
+    unsafe {
+        let library = libloading::Library::new(path).map_err(|e| format!("{path:?} - {e}"))?;
+        let f = *library.get::<unsafe extern "C" fn() -> *const ()>(symbol.as_bytes())
+            .map_err(|e| format!("{path:?} - {e}"))?;
+        let language = tree_sitter::Language::new(tree_sitter_language::LanguageFn::from_raw(f));
+
+        let version = language.abi_version();
+        if !(tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION..=tree_sitter::LANGUAGE_VERSION).contains(&version) {
+            return Err(format!("{path:?} - incompatible grammar ABI version {version}"));
+        }
+
+        Ok((library, language))
+    }
+}
+
+/// Fence attributes, line numbers are shown from `start`,
+/// `highlight` ranges count lines of the block from 1 regardless of `start`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Lines {
+    pub numbers:   bool,
+    pub start:     usize,
+    pub highlight: Vec<std::ops::RangeInclusive<usize>>,
+}
+
+impl Default for Lines {
+    fn default() -> Self {
+        Self { numbers: false, start: 1, highlight: Vec::new() }
+    }
+}
+
+/// Wraps every line into "<span class="line">" when line numbers or highlights are requested,
+/// closing and reopening highlight spans which cross line breaks
+struct LineWriter<'a, W> {
+    w:       W,
+    lines:   &'a Lines,
+    classes: Classes,
+    enabled: bool,
+    line:    usize,
+    active:  bool,
+    open:    Vec<usize>,
+}
+
+impl<'a, W: pulldown_cmark_escape::StrWrite> LineWriter<'a, W> {
+    fn new(w: W, lines: &'a Lines, classes: Classes) -> Self {
+        Self { w, lines, classes, enabled: lines.numbers || !lines.highlight.is_empty(), line: 1, active: false, open: Vec::new() }
+    }
+
+    fn span(&mut self, highlight: usize) -> Result<(), W::Error> {
+        match self.classes {
+            Classes::Inline => match crate::theme::style(ENTITIES[highlight]) {
+                style if style.is_empty() => self.w.write_str("<span>"),
+                style => write!(self.w, "<span style=\"{style}\">"),
+            },
+            classes => write!(self.w, "<span class=\"{}\">", classes.names()[highlight]),
+        }
+    }
+
+    fn start_line(&mut self) -> Result<(), W::Error> {
+        if !self.enabled || self.active {
+            return Ok(());
+        }
+        self.active = true;
+
+        let highlight = self.lines.highlight.iter().any(|r| r.contains(&self.line));
+        match (self.classes, highlight) {
+            (Classes::Inline, _) => write!(self.w, "<span style=\"{}\">", crate::theme::line_style(highlight))?,
+            (_, true) => self.w.write_str("<span class=\"line highlight\">")?,
+            (_, false) => self.w.write_str("<span class=\"line\">")?,
+        }
+        if self.lines.numbers {
+            match self.classes {
+                Classes::Inline => write!(self.w, "<span style=\"{}\" aria-hidden=\"true\">", crate::theme::line_number_style())?,
+                _ => self.w.write_str("<span class=\"line-number\" aria-hidden=\"true\">")?,
+            }
+            write!(self.w, "{}</span>", self.lines.start + self.line - 1)?;
+        }
+        for i in 0..self.open.len() {
+            self.span(self.open[i])?;
+        }
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> Result<(), W::Error> {
+        if self.enabled {
+            self.start_line()?;
+            for _ in 0..self.open.len() + 1 {
+                self.w.write_str("</span>")?;
+            }
+            self.active = false;
+        }
+        self.line += 1;
+        Ok(())
+    }
+
+    fn text(&mut self, s: &str) -> Result<(), W::Error> {
+        for part in s.split_inclusive('\n') {
+            let (text, newline) = match part.strip_suffix('\n') {
+                Some(text) => (text, true),
+                None => (part, false),
+            };
+            if !text.is_empty() {
+                self.start_line()?;
+                pulldown_cmark_escape::escape_html(&mut self.w, text)?;
+            }
+            if newline {
+                self.end_line()?;
+                self.w.write_str("\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, highlight: usize) -> Result<(), W::Error> {
+        if !self.enabled || self.active {
+            self.span(highlight)?;
+        }
+        self.open.push(highlight);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<(), W::Error> {
+        if !self.enabled || self.active {
+            self.w.write_str("</span>")?;
+        }
+        self.open.pop();
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), W::Error> {
+        if self.active {
+            self.end_line()?;
+        }
         Ok(())
     }
 }
@@ -305,9 +644,10 @@ mod tests {
         let db = LangDb::new();
 
         let report = SET.iter()
+            .filter(|(lang, _)| lang.dataset().is_some())
             .map(|(lang, input)| {
                 let mut s = String::with_capacity(input.len() * 8);
-                match db.html(input, *lang, &mut s) {
+                match db.html(input, Some(lang.name()), &Lines::default(), Classes::Short, &mut s) {
                     Ok(_) if s.contains("<span") => Ok((lang, input, s)),
                     Ok(_) => Err((lang, input, format!("Highlight error:\n{s}"))),
                     Err(e) => Err((lang, input, format!("Critical error:\n{e:?}"))),
@@ -322,4 +662,74 @@ mod tests {
             panic!("{report}================\n");
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    #[cfg(all(feature = "lang-html", feature = "lang-js", feature = "lang-css", feature = "lang-rust", feature = "lang-sql"))]
+    fn test_injections() {
+        let db = LangDb::new();
+        [
+            (Lang::Html, "<script>let a = 1;</script>",  "<span class=\"kw\">let</span>"),
+            (Lang::Html, "<style>p { color: red; }</style>", "<span class=\"prop\">color</span>"),
+            (Lang::Rust, "vec![a.len()]",                 "<span class=\"fn\">len</span>"),
+            (Lang::Rust, "db.execute(\"SELECT id FROM t\", [])",  "&quot;<span class=\"kw\">SELECT</span> id <span class=\"kw\">FROM</span>"),
+            (Lang::Rust, "sqlx::query(r#\"DELETE FROM t\"#)",    "r#&quot;<span class=\"kw\">DELETE</span>"),
+            (Lang::Rust, "println!(\"SELECT\")",                 "&quot;SELECT&quot;"),
+        ].into_iter().for_each(|(lang, input, expected)| {
+            let mut s = String::new();
+            db.html(input, Some(lang.name()), &Lines::default(), Classes::Short, &mut s).unwrap();
+            assert!(s.contains(expected), "Lang: {lang:?}\nInput: {input}\nResult: {s}");
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "lang-rust")]
+    fn test_load() {
+        let dir = crate::test_utils::TempDir::new("highlights");
+        std::fs::create_dir_all(dir.join("rs")).unwrap();
+        std::fs::write(dir.join("rs").join("highlights.scm"), "(integer_literal) @string").unwrap();
+
+        let db = LangDb::new();
+        assert_eq!(db.load(&dir), Ok(1));
+        assert!(db.load(&dir).is_err());
+
+        let mut s = String::new();
+        db.html("let a = 1;", Some("rust"), &Lines::default(), Classes::Short, &mut s).unwrap();
+        assert_eq!(s, "let a = <span class=\"str\">1</span>;");
+
+        std::fs::create_dir_all(dir.join("unknown")).unwrap();
+        assert!(LangDb::new().load(&dir).is_err());
+
+        std::fs::write(dir.join("rs").join("highlights.scm"), "(integer_literal) @string @").unwrap();
+        std::fs::remove_dir(dir.join("unknown")).unwrap();
+        assert!(LangDb::new().load(&dir).is_err());
+
+        // "<lang>.scm" replaces highlights like a "<lang>/highlights.scm", but not both at once
+        std::fs::write(dir.join("rust.scm"), "(integer_literal) @keyword").unwrap();
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+        std::fs::remove_dir_all(dir.join("rs")).unwrap();
+        let db = LangDb::new();
+        assert_eq!(db.load(&dir), Ok(1));
+        let mut s = String::new();
+        db.html("let a = 1;", Some("rust"), &Lines::default(), Classes::Short, &mut s).unwrap();
+        assert_eq!(s, "let a = <span class=\"kw\">1</span>;");
+
+        std::fs::create_dir_all(dir.join("rust")).unwrap();
+        std::fs::write(dir.join("rust").join("highlights.scm"), "(integer_literal) @string").unwrap();
+        assert!(LangDb::new().load(&dir).unwrap_err().contains("highlights are also in"));
+
+        std::fs::remove_file(dir.join("rust").join("highlights.scm")).unwrap();
+        std::fs::write(dir.join("rust").join("a.so"), "").unwrap();
+        std::fs::write(dir.join("rust").join("b.so"), "").unwrap();
+        assert!(LangDb::new().load(&dir).unwrap_err().contains("more than one compiled grammar"));
+    }
+
+    #[test]
+    #[cfg(feature = "lang-rust")]
+    fn test_classes() {
+        let db = LangDb::new();
+        let mut s = String::new();
+        db.html("fn a() { return 1; }", Some("rust"), &Lines::default(), Classes::Full, &mut s).unwrap();
+        assert!(s.contains("<span class=\"keyword\">return</span>"), "{s}");
+        assert!(s.contains("<span class=\"constant.builtin\">1</span>"), "{s}");
+    }
+}